@@ -1,7 +1,7 @@
 #!/usr/bin/env -S cargo +nightly -Zscript
 ---cargo
 [dependencies]
-xml = "1.0"
+wiktionary-hatsuon = { path = "..", features = ["xml"] }
 
 [profile.dev]
 opt-level = 3
@@ -22,37 +22,11 @@ fn main() {
 	let output_path = directory.join("enwiktionary.bin");
 	let mut output = std::fs::OpenOptions::new().write(true).create_new(true).open(output_path).unwrap();
 
+	// The pages are filtered as the xml feature reads them, so that both give the same records.
 	for file in files {
-		let input = std::io::BufReader::new(std::fs::File::open(file).unwrap());
-		let mut parser = xml::reader::EventReader::new(input);
-		let mut title = None;
-		let mut title_text: Vec<(String, String)> = Vec::new();
-		loop {
-			match parser.next() {
-				Ok(xml::reader::XmlEvent::StartElement { name, .. }) => {
-					if name.local_name == "title"
-						&& let Ok(xml::reader::XmlEvent::Characters(s)) = parser.next()
-					{
-						title = Some(s);
-					} else if name.local_name == "text"
-						&& let Ok(xml::reader::XmlEvent::Characters(s)) = parser.next()
-						&& title.as_ref().is_some_and(|title| {
-							!(title.starts_with("User:")
-								|| title.starts_with("Wiktionary:")
-								|| title.starts_with("User talk:")
-								|| title.starts_with("Appendix:"))
-								&& s.contains("==Japanese==\n")
-						}) {
-						title_text.push((title.take().unwrap(), s));
-					};
-				},
-				Ok(xml::reader::XmlEvent::EndDocument) => break,
-				Err(e) => panic!("{e}"),
-				_ => {},
-			}
-		}
-		for (title, text) in title_text {
+		for page in wiktionary_hatsuon::dump::open_xml(&file).unwrap() {
 			use std::io::Write as _;
+			let (title, text) = page.unwrap_or_else(|e| panic!("{e}"));
 			let title = title.as_bytes();
 			let text = text.as_bytes();
 			output.write_all(&(title.len() as u64).to_le_bytes()).unwrap();
@@ -0,0 +1,135 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	io::Write as _,
+	sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+	Note,
+	Warning,
+}
+
+impl Level {
+	fn label(self) -> &'static str {
+		match self {
+			Level::Note => "note",
+			Level::Warning => "warning",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub level: Level,
+	pub title: String,
+	pub message: String,
+}
+
+// A thread-safe collector of diagnostics, to be emitted after processing so that messages from
+// concurrent workers do not interleave. Besides messages, it tallies events too common to report one by one.
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+	diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+	tallies: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+impl Diagnostics {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&self, level: Level, title: &str, message: impl Into<String>) {
+		let diagnostic = Diagnostic { level, title: title.to_owned(), message: message.into() };
+		self.diagnostics.lock().unwrap().push(diagnostic);
+	}
+
+	// Count an occurrence of an event (e.g. a kind of failure), to be reported only in total.
+	pub fn tally(&self, event: impl Into<String>) {
+		*self.tallies.lock().unwrap().entry(event.into()).or_default() += 1;
+	}
+
+	// Return the number of occurrences of each tallied event, by event, without removing them.
+	pub fn tallies(&self) -> BTreeMap<String, usize> {
+		self.tallies.lock().unwrap().clone()
+	}
+
+	pub fn note(&self, title: &str, message: impl Into<String>) {
+		self.push(Level::Note, title, message);
+	}
+
+	pub fn warn(&self, title: &str, message: impl Into<String>) {
+		self.push(Level::Warning, title, message);
+	}
+
+	// Remove and return all collected diagnostics, grouped by title in order of first appearance.
+	pub fn take(&self) -> Vec<Diagnostic> {
+		let diagnostics = std::mem::take(&mut *self.diagnostics.lock().unwrap());
+		let mut groups: Vec<Vec<Diagnostic>> = Vec::new();
+		let mut indices: HashMap<String, usize> = HashMap::new();
+		for diagnostic in diagnostics {
+			let i = *indices.entry(diagnostic.title.clone()).or_insert_with(|| {
+				groups.push(Vec::new());
+				groups.len() - 1
+			});
+			groups[i].push(diagnostic);
+		}
+		groups.into_iter().flatten().collect()
+	}
+
+	// Return the messages of all collected diagnostics of a given level, keyed by title, without removing them.
+	pub fn messages_by_title(&self, level: Level) -> HashMap<String, Vec<String>> {
+		let mut messages: HashMap<String, Vec<String>> = HashMap::new();
+		for diagnostic in self.diagnostics.lock().unwrap().iter().filter(|x| x.level == level) {
+			messages.entry(diagnostic.title.clone()).or_default().push(diagnostic.message.clone());
+		}
+		messages
+	}
+
+	// Write all collected diagnostics to stderr, grouped by title, and then all tallies.
+	pub fn emit(&self) {
+		let mut stderr = std::io::stderr().lock();
+		let mut last_title: Option<String> = None;
+		for diagnostic in self.take() {
+			if last_title.as_ref() != Some(&diagnostic.title) {
+				writeln!(stderr, "{}:", diagnostic.title).unwrap();
+				last_title = Some(diagnostic.title.clone());
+			}
+			writeln!(stderr, "\t{}: {}", diagnostic.level.label(), diagnostic.message).unwrap();
+		}
+		for (event, count) in std::mem::take(&mut *self.tallies.lock().unwrap()) {
+			writeln!(stderr, "{event}: {count}").unwrap();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn diagnostics_from_several_threads_are_all_collected() {
+		let diagnostics = Diagnostics::new();
+		std::thread::scope(|scope| {
+			for i in 0..8 {
+				let diagnostics = diagnostics.clone();
+				scope.spawn(move || {
+					for j in 0..100 {
+						diagnostics.warn(&format!("title {i}"), format!("message {j}"));
+						diagnostics.tally("event");
+					}
+				});
+			}
+		});
+		let collected = diagnostics.take();
+		assert_eq!(collected.len(), 800);
+		assert_eq!(diagnostics.tallies()["event"], 800);
+		// Each title's messages are grouped together, in the order that the title's thread pushed them.
+		for (i, group) in collected.chunks(100).enumerate() {
+			let title = &group[0].title;
+			assert!(group.iter().all(|x| &x.title == title), "group {i} mixes titles");
+			let messages: Vec<_> = group.iter().map(|x| x.message.clone()).collect();
+			assert_eq!(messages, (0..100).map(|j| format!("message {j}")).collect::<Vec<_>>());
+		}
+	}
+}
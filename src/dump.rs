@@ -0,0 +1,136 @@
+use std::{
+	fs::File,
+	io::{self, BufReader, Read},
+	path::Path,
+};
+
+use bzip2::read::MultiBzDecoder;
+use xml::reader::{EventReader, XmlEvent};
+
+// Iterator over the Japanese pages of an XML dump, as titles and texts.
+// scripts/filter_wiktionary.rs writes these pages as the records read without the xml feature.
+pub struct XmlPages<R: Read> {
+	parser: EventReader<R>,
+	title: Option<String>,
+	is_done: bool,
+}
+
+impl<R: Read> XmlPages<R> {
+	pub fn new(input: R) -> Self {
+		Self { parser: EventReader::new(input), title: None, is_done: false }
+	}
+}
+
+// Open an XML dump, decompressing it if it is bzip2-compressed (e.g. *-pages-articles.xml.bz2).
+pub fn open_xml(path: &Path) -> io::Result<XmlPages<Box<dyn Read>>> {
+	let input = BufReader::new(File::open(path)?);
+	let input: Box<dyn Read> = if path.extension().is_some_and(|x| x == "bz2") {
+		// NOTE: Wikimedia dumps are multistream, so decoding may not stop after the first stream.
+		Box::new(BufReader::new(MultiBzDecoder::new(input)))
+	} else {
+		Box::new(input)
+	};
+	Ok(XmlPages::new(input))
+}
+
+// Whether a page may have Japanese entries, being outside the project namespaces and having a Japanese section.
+pub fn is_japanese_page(title: &str, text: &str) -> bool {
+	!(title.starts_with("User:")
+		|| title.starts_with("Wiktionary:")
+		|| title.starts_with("User talk:")
+		|| title.starts_with("Appendix:"))
+		&& text.contains("==Japanese==\n")
+}
+
+impl<R: Read> Iterator for XmlPages<R> {
+	type Item = Result<(String, String), xml::reader::Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while !self.is_done {
+			let event = match self.parser.next() {
+				Ok(XmlEvent::StartElement { name, .. })
+					if matches!(name.local_name.as_str(), "title" | "text") =>
+				{
+					self.parser.next().map(|x| (Some(name.local_name), x))
+				},
+				Ok(XmlEvent::EndDocument) => {
+					self.is_done = true;
+					continue;
+				},
+				event => event.map(|x| (None, x)),
+			};
+			match event {
+				Ok((Some(name), XmlEvent::Characters(s))) if name == "title" => self.title = Some(s),
+				Ok((Some(name), XmlEvent::Characters(text))) if name == "text" => {
+					if let Some(title) = self.title.take()
+						&& is_japanese_page(&title, &text)
+					{
+						return Some(Ok((title, text)));
+					}
+				},
+				Ok(_) => (),
+				Err(e) => {
+					self.is_done = true;
+					return Some(Err(e));
+				},
+			}
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use bzip2::{Compression, write::BzEncoder};
+
+	use super::*;
+
+	const DUMP: &str = r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.11/">
+	<page>
+		<title>手紙</title>
+		<revision><text xml:space="preserve">==Japanese==
+{{ja-pron|てがみ|acc=0}}
+</text></revision>
+	</page>
+	<page>
+		<title>User:Someone</title>
+		<revision><text>==Japanese==
+</text></revision>
+	</page>
+	<page>
+		<title>dog</title>
+		<revision><text>==English==
+</text></revision>
+	</page>
+</mediawiki>"#;
+
+	fn titles(pages: impl Iterator<Item = Result<(String, String), xml::reader::Error>>) -> Vec<String> {
+		pages.map(|x| x.unwrap().0).collect()
+	}
+
+	#[test]
+	fn only_japanese_pages_are_read() {
+		let pages: Vec<_> = XmlPages::new(DUMP.as_bytes()).map(Result::unwrap).collect();
+		assert_eq!(pages, [("手紙".to_owned(), "==Japanese==\n{{ja-pron|てがみ|acc=0}}\n".to_owned())]);
+	}
+
+	#[test]
+	fn compressed_dumps_are_decompressed() {
+		let path = std::env::temp_dir().join(format!("hatsuon-test-{}.xml.bz2", std::process::id()));
+		let mut encoder = BzEncoder::new(File::create(&path).unwrap(), Compression::fast());
+		encoder.write_all(DUMP.as_bytes()).unwrap();
+		encoder.finish().unwrap();
+		let pages = titles(open_xml(&path).unwrap());
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(pages, ["手紙"]);
+	}
+
+	#[test]
+	fn malformed_xml_ends_the_pages() {
+		let mut pages = XmlPages::new("<mediawiki><page><title>手紙</title></mediawiki>".as_bytes());
+		assert!(matches!(pages.next(), Some(Err(_))));
+		assert!(pages.next().is_none());
+	}
+}
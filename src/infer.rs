@@ -1,57 +1,186 @@
-use std::collections::HashSet;
+use std::{
+	collections::{HashMap, HashSet},
+	mem,
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-	ja::{compute_duration, expand_katakana, is_ideograph, try_consume_kana, try_katakanify},
-	parse::{JaKanjitab, JaPos, JaPron, JaPronAccent},
-	wikitext::remove_links,
+	diagnostics::Diagnostics,
+	ja::{
+		compute_duration, count_ideographs, expand_katakana, is_ideograph, merge_yotsugana, segment_moras,
+		split_moras, try_consume_kana, try_katakanify, try_katakanify_report, voice,
+	},
+	parse::{AccentReference, JaKanjitab, JaPos, JaPron, JaPronAccent, JaUsex, Yomi},
+	wikitext::{remove_links, strip_html_tags},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DecompositionInfo {
 	pub atoms: Vec<Atom>,
+	pub reference: Option<String>, // The source cited by the kanjitab, if any.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub yomi: Option<Yomi>,
 }
 
 impl DecompositionInfo {
 	pub fn reading(&self) -> String {
-		self.atoms
-			.iter()
-			.map(|x| match x {
-				Atom::Ruby { reading, .. } => reading.to_owned(),
-				Atom::Unknown(c) => c.to_string(),
-				Atom::Kana(kana) => kana.to_owned(),
+		atoms_reading(&self.atoms)
+	}
+
+	// Returns each kanji of the title that has a reading of its own, paired with that reading.
+	// Kanji under a shared reading (e.g. jukujikun) and iteration marks are skipped.
+	pub fn kanji_readings(&self) -> Vec<(char, &str)> {
+		let mut kanji_readings = Vec::new();
+		for atom in &self.atoms {
+			if let Atom::Ruby { base, reading, .. } = atom
+				&& let [c] = base.chars().collect::<Vec<_>>()[..]
+				&& is_ideograph(c)
+				&& c != '\u{3005}'
+				&& !reading.is_empty()
+			{
+				kanji_readings.push((c, reading.as_str()));
+			}
+		}
+		kanji_readings
+	}
+
+	// Returns the atoms either as given, or with each run of adjacent single-kanji ruby atoms merged into one.
+	// Slots spanning several kanji (e.g. jukujikun) or none (e.g. omissions) are kept apart.
+	pub fn grouped(&self, mode: GroupMode) -> Vec<Atom> {
+		match mode {
+			GroupMode::Separate => self.atoms.clone(),
+			GroupMode::Runs => {
+				let mut grouped: Vec<Atom> = Vec::with_capacity(self.atoms.len());
+				let mut is_run = false; // Whether the last grouped atom is a run of single-kanji ruby atoms.
+				for atom in &self.atoms {
+					let is_single = matches!(atom, Atom::Ruby { character_count: 1, .. });
+					if is_run
+						&& let Atom::Ruby { character_count: 1, base, reading, sound_change } = atom
+						&& let Some(Atom::Ruby {
+							character_count: run_count,
+							base: run_base,
+							reading: run_reading,
+							sound_change: run_sound_change,
+						}) = grouped.last_mut()
+					{
+						*run_count += 1;
+						run_base.push_str(base);
+						run_reading.push_str(reading);
+						*run_sound_change = run_sound_change.or(*sound_change);
+					} else {
+						grouped.push(atom.clone());
+					}
+					is_run = is_single;
+				}
+				grouped
+			},
+		}
+	}
+
+	// Render the decomposition as HTML, with each reading as furigana over its base.
+	// Kana are given as stored, in katakana, and an omitted reading is attached to the preceding one.
+	pub fn to_ruby_html(&self) -> String {
+		let mut segments: Vec<(String, Option<String>)> = Vec::with_capacity(self.atoms.len());
+		for atom in &self.atoms {
+			match atom {
+				Atom::Ruby { base, reading, .. } if base.is_empty() => match segments.last_mut() {
+					Some((_, Some(last))) => last.push_str(reading),
+					_ => segments.push((reading.clone(), None)),
+				},
+				Atom::Ruby { base, reading, .. } if reading.is_empty() => segments.push((base.clone(), None)),
+				Atom::Ruby { base, reading, .. } => segments.push((base.clone(), Some(reading.clone()))),
+				Atom::Unknown(c) => segments.push((c.to_string(), None)),
+				Atom::Kana(kana) => segments.push((kana.clone(), None)),
+			}
+		}
+		segments
+			.into_iter()
+			.map(|(base, reading)| match reading {
+				Some(reading) => format!("<ruby>{base}<rt>{reading}</rt></ruby>"),
+				None => base,
 			})
 			.collect()
 	}
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum GroupMode {
+	Separate, // One atom per kanjitab slot.
+	Runs,     // One atom per run of consecutive kanji with a reading each.
+}
+
 #[derive(Debug)]
 pub enum DecompositionError {
 	Incomplete,   // The kanjitab is incomplete. This is likely an error in the source article.
 	Empty,        // The kanjitab has no readings.
 	Mismatch,     // The kanjitab does not match a reading. This is likely an error in the source article.
 	Unconsidered, // Due to complications, this decomposition is ignored.
+	Orphaned,     // An iteration mark 々 does not follow a kanji. This is likely an error in the source article.
+	// An omission (o1=, ...) follows no kanji with a reading. This is likely an error in the source article.
+	MisplacedOmission,
 }
 
 // A segment of a reading, consisting of a string of katakana and the number of characters it represents.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
 pub enum Atom {
-	Ruby { character_count: u8, reading: String },
+	// The base is as written in the title.
+	Ruby {
+		character_count: u8,
+		base: String,
+		reading: String,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		sound_change: Option<SoundChange>,
+	},
 	Unknown(char),
 	Kana(String),
 }
 
+// A change in the reading of a kanji due to its position in a compound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundChange {
+	Rendaku,   // Sequential voicing (e.g. 紙 as ガミ in 手紙).
+	Sokuonbin, // Gemination of the final mora (e.g. 一 as イッ in 一緒).
+}
+
+// Classify the change from the reading of a kanji to an altered reading, if it is a known sound change.
+fn classify_sound_change(reading: &str, altered: &str) -> Option<SoundChange> {
+	if voice(reading).is_some_and(|x| x == altered) {
+		Some(SoundChange::Rendaku)
+	} else if let Some(stem) = altered.strip_suffix('ッ')
+		&& let Some(tail) = reading.strip_prefix(stem)
+		&& split_moras(tail).len() == 1
+	{
+		Some(SoundChange::Sokuonbin)
+	} else {
+		None
+	}
+}
+
+fn atoms_reading(atoms: &[Atom]) -> String {
+	atoms
+		.iter()
+		.map(|x| match x {
+			Atom::Ruby { reading, .. } => reading.to_owned(),
+			Atom::Unknown(c) => c.to_string(),
+			Atom::Kana(kana) => kana.to_owned(),
+		})
+		.collect()
+}
+
 pub fn infer_decompositions(
-	title: &str, ja_kanjitab: JaKanjitab, readings: &HashSet<String>,
+	title: &str, ja_kanjitab: JaKanjitab, readings: &HashSet<String>, diagnostics: &Diagnostics,
 ) -> Result<DecompositionInfo, DecompositionError> {
 	if ja_kanjitab.readings.is_empty() {
 		if !(ja_kanjitab.alterations.is_empty() && ja_kanjitab.omissions.is_empty()) {
-			println!("bad: {title}")
+			diagnostics.warn(title, "kanjitab has alterations or omissions but no readings");
 		}
 		return Err(DecompositionError::Empty);
 	}
 
 	assert!(ja_kanjitab.readings.len() >= ja_kanjitab.alterations.len());
-	assert!(ja_kanjitab.readings.len() >= ja_kanjitab.omissions.len());
 
 	let mut atoms = Vec::new();
 	let mut kanji_cursor = 0;
@@ -61,13 +190,30 @@ pub fn infer_decompositions(
 	};
 
 	let mut chars = kata_title.chars();
+	let mut previous = None;
 	while let Some(c) = chars.next() {
-		if is_ideograph(c) {
+		if c == '\u{3005}' && !previous.is_some_and(is_ideograph) {
+			return Err(DecompositionError::Orphaned);
+		}
+		previous = Some(c);
+		if c == '\u{3005}' && kanji_cursor >= ja_kanjitab.readings.len() {
+			// NOTE: A kanjitab may give no reading for 々 (e.g. 人々 with only ひと), which then repeats the
+			//       reading of the kanji it follows, possibly with rendaku, as determined by alignment.
+			atoms.push(Atom::Unknown(c));
+		} else if is_ideograph(c) {
 			let Some((reading, character_count)) = ja_kanjitab.readings.get(kanji_cursor) else {
 				return Err(DecompositionError::Incomplete);
 			};
-			let reading =
-				ja_kanjitab.alterations.get(kanji_cursor).and_then(Option::as_ref).unwrap_or(reading);
+			let alteration = ja_kanjitab.alterations.get(kanji_cursor).and_then(Option::as_ref);
+			let sound_change = alteration
+				.and_then(|x| try_katakanify(x, |c| c.is_whitespace(), |_| false))
+				.zip(try_katakanify(reading, |c| c.is_whitespace(), |_| false))
+				.and_then(|(altered, reading)| classify_sound_change(&reading, &altered));
+			let reading = alteration.unwrap_or(reading);
+			// An empty reading leaves its kanji without any reading to align, even if it spans several.
+			if reading.is_empty() {
+				return Err(DecompositionError::Incomplete);
+			}
 			let reading = if reading == "ー" || reading == "-" {
 				// NOTE: "大元帥" and "鸕鷀草葺不合尊".
 				"".to_owned()
@@ -75,17 +221,29 @@ pub fn infer_decompositions(
 				try_katakanify(reading, |c| c.is_whitespace(), |_| false)
 					.ok_or(DecompositionError::Unconsidered)?
 			};
-			atoms.push(Atom::Ruby { character_count: *character_count, reading: reading.clone() });
+			let mut base = c.to_string();
+			for _ in 1..*character_count {
+				let Some(kanji) = chars.next().filter(|&x| is_ideograph(x)) else {
+					return Err(DecompositionError::Mismatch);
+				};
+				base.push(kanji);
+				previous = Some(kanji);
+			}
+			atoms.push(Atom::Ruby {
+				character_count: *character_count,
+				base,
+				reading: reading.clone(),
+				sound_change,
+			});
 			if let Some(Some(omission)) = ja_kanjitab.omissions.get(kanji_cursor) {
 				atoms.push(Atom::Ruby {
 					character_count: 0,
-					reading: try_katakanify(omission, |_| false, |_| false).unwrap(),
+					base: String::new(),
+					reading: try_katakanify(omission, |_| false, |_| false)
+						.ok_or(DecompositionError::Unconsidered)?,
+					sound_change: None,
 				});
 			}
-			for _ in 1..*character_count {
-				let _kanji = chars.next().unwrap();
-				assert!(is_ideograph(_kanji));
-			}
 			kanji_cursor += 1;
 		} else if c == 'ヶ' {
 			atoms.push(Atom::Unknown(c));
@@ -96,41 +254,76 @@ pub fn infer_decompositions(
 		}
 	}
 
+	// Every omission must follow a kanji with a reading, or it would be left out of the decomposition.
+	if ja_kanjitab.omissions.iter().skip(kanji_cursor).any(Option::is_some) {
+		return Err(DecompositionError::MisplacedOmission);
+	}
+
 	assert!(
 		atoms
 			.iter()
 			.map(|x| match x {
 				Atom::Ruby { character_count, .. } => *character_count as u64,
+				Atom::Unknown('\u{3005}') => 1,
 				Atom::Unknown(_) => 0,
 				Atom::Kana(_) => 0,
 			})
 			.sum::<u64>()
-			== title.chars().map(is_ideograph).map(|x| x as u64).sum()
+			== count_ideographs(title) as u64
 	);
 
 	// NOTE: The presence of unused empty readings may indicate a non-fatal source error.
 	assert!(ja_kanjitab.readings[kanji_cursor..].iter().all(|x| x.0.is_empty()));
 
-	let Some(replacements) = align(&atoms, readings) else {
+	// NOTE: Gemination is often left out of kanjitab readings (e.g. 学期 with がく as ガッキ).
+	let Some(replacements) = align(&atoms, readings, false).or_else(|| align(&atoms, readings, true)) else {
 		return Err(DecompositionError::Mismatch);
 	};
 
 	for (i, reading) in replacements {
-		atoms[i] = Atom::Ruby { character_count: 1, reading }
+		atoms[i] = match &atoms[i] {
+			Atom::Ruby { character_count, base, reading: original, .. } => {
+				let sound_change = classify_sound_change(original, &reading);
+				Atom::Ruby { character_count: *character_count, base: base.clone(), reading, sound_change }
+			},
+			Atom::Unknown(c) => {
+				// A repeated reading may be voiced (e.g. 人々 as ヒトビト).
+				let last = atoms[..i].iter().rev().find_map(|x| match x {
+					Atom::Ruby { reading, .. } => Some(reading),
+					_ => None,
+				});
+				let sound_change =
+					last.filter(|_| *c == '\u{3005}').and_then(|x| classify_sound_change(x, &reading));
+				Atom::Ruby { character_count: 1, base: c.to_string(), reading, sound_change }
+			},
+			_ => Atom::Kana(reading),
+		}
 	}
 
-	Ok(DecompositionInfo { atoms })
+	Ok(DecompositionInfo { atoms, reference: ja_kanjitab.reference, yomi: ja_kanjitab.yomi })
 }
 
-fn align(candidate: &[Atom], readings: &HashSet<String>) -> Option<Vec<(usize, String)>> {
+// Align atoms to one of the readings, returning the replacement reading of any atom that differs.
+// When fuzzy, a ruby reading ending in ク or ツ may also match with ッ in its place.
+fn align(candidate: &[Atom], readings: &HashSet<String>, is_fuzzy: bool) -> Option<Vec<(usize, String)>> {
 	'reading: for reading in readings {
 		let mut remaining = reading.as_str();
 		let mut replacements = Vec::new();
+		let mut last_ruby: Option<&str> = None;
 		for (i, atom) in candidate.iter().enumerate() {
 			match atom {
 				Atom::Ruby { reading, .. } => {
 					if let Some(then) = remaining.strip_prefix(reading.as_str()) {
-						remaining = then
+						remaining = then;
+						last_ruby = Some(reading);
+					} else if is_fuzzy
+						&& let Some(stem) = reading.strip_suffix(['ク', 'ツ'])
+						&& let Some(then) = remaining.strip_prefix(stem).and_then(|x| x.strip_prefix('ッ'))
+					{
+						let (matched, _) = remaining.split_at(remaining.len() - then.len());
+						remaining = then;
+						last_ruby = Some(matched);
+						replacements.push((i, matched.to_owned()));
 					} else {
 						continue 'reading;
 					}
@@ -147,16 +340,32 @@ fn align(candidate: &[Atom], readings: &HashSet<String>) -> Option<Vec<(usize, S
 							continue 'reading;
 						}
 					},
+					'\u{3005}' => {
+						let Some(last) = last_ruby.filter(|x| !x.is_empty()) else {
+							continue 'reading;
+						};
+						let repeated = [Some(last.to_owned()), voice(last)]
+							.into_iter()
+							.flatten()
+							.find(|x| remaining.starts_with(x.as_str()));
+						let Some(repeated) = repeated else {
+							continue 'reading;
+						};
+						let (matched, then) = remaining.split_at(repeated.len());
+						last_ruby = Some(matched);
+						remaining = then;
+						replacements.push((i, repeated));
+					},
 					_ => continue 'reading,
 				},
 				Atom::Kana(kana) => {
 					if let Some(then) = remaining.strip_prefix(kana.as_str()) {
 						remaining = then;
-					} else if kana == "ヅ"
-						&& let Some(then) = remaining.strip_prefix("ズ")
+					} else if let Some((matched, then)) = remaining.split_at_checked(kana.len())
+						&& merge_yotsugana(matched) == merge_yotsugana(kana)
 					{
 						remaining = then;
-						replacements.push((i, "ズ".to_owned()));
+						replacements.push((i, matched.to_owned()));
 					} else {
 						continue 'reading;
 					}
@@ -168,22 +377,214 @@ fn align(candidate: &[Atom], readings: &HashSet<String>) -> Option<Vec<(usize, S
 	None
 }
 
+// Predict the accent of a compound from the accent of its final element, looked up by reading, following
+// the usual rules for compound nouns: a final element of up to two moras places the accent on the last mora
+// of the initial element; one of three or four moras keeps its own medial accent, and otherwise places the
+// accent on its first mora; a longer one keeps its own accent. The accent never falls on a special mora.
+pub fn predict_compound_accent(atoms: &[Atom], component_accents: &HashMap<String, Vec<u8>>) -> Option<u8> {
+	let (split, final_accent) = (1..atoms.len()).find_map(|i| {
+		atoms.get(i).filter(|x| matches!(x, Atom::Ruby { .. }))?;
+		Some((i, *component_accents.get(&atoms_reading(&atoms[i..]))?.first()?))
+	})?;
+	let initial = compute_duration(&atoms_reading(&atoms[..split]));
+	let final_reading = atoms_reading(&atoms[split..]);
+	let final_duration = compute_duration(&final_reading);
+	let accent = match final_duration {
+		..=2 => initial,
+		3..=4 if final_accent == 0 || final_accent as usize == final_duration => initial + 1,
+		_ if final_accent == 0 => return Some(0),
+		_ => initial + final_accent as usize,
+	};
+	let reading = atoms_reading(atoms);
+	let moras = split_moras(&reading);
+	let accent = if accent > 1 && matches!(moras.get(accent - 1), Some(&("ー" | "ン" | "ッ"))) {
+		accent - 1
+	} else {
+		accent
+	};
+	accent.try_into().ok()
+}
+
 pub fn pos_reading_ignore(c: char) -> bool {
 	matches!(c, '.' | '%' | '-' | '\u{2010}' | '\u{30A0}' | '\u{30FB}' | '^' | '\'') || c.is_whitespace()
 }
 
-pub fn infer_pos_readings(ja_pos: JaPos) -> Vec<String> {
+// Returns the readings of a part-of-speech template, without duplicates, in the order given.
+pub fn infer_pos_readings(title: &str, ja_pos: JaPos, diagnostics: &Diagnostics) -> Vec<String> {
 	let mut readings = Vec::new();
 	for reading in ja_pos.readings {
-		readings.extend(try_katakanify(&remove_links(&reading), pos_reading_ignore, |_| false));
+		match try_katakanify_report(&strip_html_tags(&remove_links(&reading)), pos_reading_ignore, |_| false)
+		{
+			Ok(reading) if readings.contains(&reading) => continue,
+			Ok(reading) => readings.push(reading),
+			Err(c) => report_rejected_reading(title, &reading, c, diagnostics),
+		}
 	}
 	readings
 }
 
+fn report_rejected_reading(title: &str, reading: &str, c: char, diagnostics: &Diagnostics) {
+	diagnostics.warn(title, format!("reading {reading} rejected due to char {c:?} in title {title}"));
+}
+
+fn usex_reading_ignore(c: char) -> bool {
+	pos_reading_ignore(c) || matches!(c, '\u{3001}' | '\u{3002}' | '\u{300C}' | '\u{300D}' | '！' | '？')
+}
+
+// Pairs each whitespace-separated word of an example with its reading, as given by its furigana annotation
+// (e.g. 漢字^かんじ), or by the reading of the example if it is segmented identically; otherwise, the whole
+// example is paired with the whole reading. Annotated examples give the words without annotations kana-only
+// readings, if they are written in kana.
+pub fn infer_usex_words(ja_usex: JaUsex) -> Vec<(String, Option<String>)> {
+	let kana = |x: &str| try_katakanify(x, usex_reading_ignore, |_| false);
+	let words: Vec<(&str, Option<&str>)> = ja_usex
+		.japanese
+		.split_whitespace()
+		.map(|x| match x.split_once('^') {
+			Some((word, annotation)) if !word.is_empty() => (word, Some(annotation)),
+			_ => (x, None),
+		})
+		.collect();
+	let word_readings: Vec<&str> = ja_usex.reading.iter().flat_map(|x| x.split_whitespace()).collect();
+	if words.iter().any(|(_, annotation)| annotation.is_some()) {
+		words
+			.into_iter()
+			.map(|(word, annotation)| (word.to_owned(), annotation.map_or_else(|| kana(word), kana)))
+			.collect()
+	} else if ja_usex.reading.is_some() && words.len() == word_readings.len() {
+		words
+			.into_iter()
+			.zip(word_readings)
+			.map(|((word, _), reading)| (word.to_owned(), kana(reading)))
+			.collect()
+	} else {
+		let text = words.into_iter().map(|(word, _)| word).collect();
+		vec![(text, ja_usex.reading.as_deref().and_then(kana))]
+	}
+}
+
 #[derive(Debug)]
 pub struct AccentInfo {
 	pub reading: String,
+	// The number of moras before the downstep: 0 is heiban, and the mora count of the reading is odaka.
+	// This never exceeds the mora count of the reading.
 	pub accent: Option<u8>,
+	pub audio: Vec<String>,
+	pub qualifiers: Vec<String>, // The qualifiers of the reading (e.g. dated), if it is given.
+	pub references: Vec<AccentReference>,
+	pub dialect: Dialect,
+	pub devoicing: Option<Vec<bool>>, // Whether each mora is devoiced, if the source says.
+	// Whether the word is conjugable, in which case the accent is that of its dictionary form.
+	pub is_yougen: bool,
+}
+
+// The dialect of an accent, serialized by its name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dialect {
+	#[default]
+	Tokyo,
+	Keihan,        // Kyoto, Osaka, and the rest of the Kansai region.
+	Other(String), // Any other region, as given.
+}
+
+impl Dialect {
+	// Classify the region given by an accent location parameter (acc_loc=, acc2_loc=, ...). Tokyo and Kansai
+	// are recognized by their English or Japanese names, or those of their cities, and any other region is
+	// kept as given. An accent without a location, or with an empty one, is Tokyo.
+	pub fn from_location(location: &str) -> Dialect {
+		match location.trim() {
+			"" | "Tokyo" | "東京" => Dialect::Tokyo,
+			"Kyoto" | "Osaka" | "Kansai" | "Keihan" | "京都" | "大阪" | "関西" | "京阪" => {
+				Dialect::Keihan
+			},
+			location => Dialect::Other(location.to_owned()),
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		match self {
+			Dialect::Tokyo => "tokyo",
+			Dialect::Keihan => "keihan",
+			Dialect::Other(location) => location,
+		}
+	}
+}
+
+impl Serialize for Dialect {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.name())
+	}
+}
+
+impl<'de> Deserialize<'de> for Dialect {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Ok(match String::deserialize(deserializer)?.as_str() {
+			"tokyo" => Dialect::Tokyo,
+			"keihan" => Dialect::Keihan,
+			location => Dialect::Other(location.to_owned()),
+		})
+	}
+}
+
+// The category of an accent, by the position of its downstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccentType {
+	Heiban,    // No downstep.
+	Atamadaka, // A downstep after the first mora.
+	Nakadaka,  // A downstep after a medial mora.
+	Odaka,     // A downstep after the last mora, heard only on a following particle.
+}
+
+impl AccentInfo {
+	// NOTE: An accent on the only mora of a reading is classified as atamadaka, as is conventional.
+	pub fn accent_type(&self) -> Option<AccentType> {
+		let accent = self.accent? as usize;
+		Some(match accent {
+			0 => AccentType::Heiban,
+			1 => AccentType::Atamadaka,
+			_ if accent == compute_duration(&self.reading) => AccentType::Odaka,
+			_ => AccentType::Nakadaka,
+		})
+	}
+}
+
+// Convert an accent of any source form to the convention of `AccentInfo`, discarding it if it would
+// place the downstep past the end of the reading.
+fn normalize_accent(
+	title: &str, accent: JaPronAccent, reading: &str, diagnostics: &Diagnostics,
+) -> Option<u8> {
+	let duration = compute_duration(reading);
+	let accent = match accent {
+		JaPronAccent::Numeric(n) => n,
+		// NOTE: Unlike other accents, an odaka accent may fall on a special mora, as the downstep is then
+		//       only realized on a following particle (e.g. on a final ン or ー).
+		JaPronAccent::Odaka => duration.try_into().unwrap(),
+		JaPronAccent::None => return None,
+	};
+	if accent as usize > duration {
+		diagnostics.warn(title, format!("accent {accent} exceeds the {duration} moras of reading {reading}"));
+		return None;
+	}
+	Some(accent)
+}
+
+// Remove the underscores that mark the following mora of a reading as devoiced, returning the devoicing of
+// each mora if any are marked.
+fn strip_devoicing_marks(reading: &str) -> (String, Option<Vec<bool>>) {
+	if !reading.contains('_') {
+		return (reading.to_owned(), None);
+	}
+	let mut stripped = String::with_capacity(reading.len());
+	let mut marks = HashSet::new();
+	for c in reading.chars() {
+		if c == '_' {
+			marks.insert(stripped.len());
+		} else {
+			stripped.push(c);
+		}
+	}
+	let devoicing = segment_moras(&stripped).iter().map(|x| marks.contains(&x.byte_range.start)).collect();
+	(stripped, Some(devoicing))
 }
 
 pub fn reading_ignore(c: char) -> bool {
@@ -191,71 +592,499 @@ pub fn reading_ignore(c: char) -> bool {
 }
 
 // Returns a list of kana readings (with duplicates) and an optional accent nucleus position for each.
-pub fn infer_accent(title: &str, ja_pron: JaPron) -> Vec<AccentInfo> {
+pub fn infer_accent(title: &str, ja_pron: JaPron, diagnostics: &Diagnostics) -> Vec<AccentInfo> {
+	infer_accent_verbose(title, ja_pron, diagnostics).into_iter().filter_map(Result::ok).collect()
+}
+
+// A reading of a pronunciation template that was dropped, as given.
+#[derive(Debug)]
+pub struct AccentWarning {
+	pub reading: String,
+	pub reason: AccentWarningReason,
+}
+
+#[derive(Debug)]
+pub enum AccentWarningReason {
+	Katakanify(char), // The reading contains a character that is not kana.
+	ExpandKatakana,   // The reading has an iteration mark that cannot be expanded.
+}
+
+// Like infer_accent, but keeps each dropped reading in place, with the reason it was dropped.
+// NOTE: Non-Tokyo accents are not dropped, but kept with their dialect.
+pub fn infer_accent_verbose(
+	title: &str, mut ja_pron: JaPron, diagnostics: &Diagnostics,
+) -> Vec<Result<AccentInfo, AccentWarning>> {
 	enum Reading {
 		Fallback,
-		Error,
-		Actual(String),
+		Error(AccentWarning),
+		Actual(String, Option<Vec<bool>>),
 	}
 	let mut readings = Vec::new();
 
-	for reading in ja_pron.readings {
+	for reading in mem::take(&mut ja_pron.readings) {
 		if reading.is_empty() {
 			readings.push(Reading::Fallback);
 		} else {
 			readings.push(
-				try_katakanify(&reading, reading_ignore, |_| false)
-					.and_then(|x| expand_katakana(&x))
-					.map_or(Reading::Error, Reading::Actual),
+				match try_katakanify_report(&strip_html_tags(&reading), reading_ignore, |c| c == '_') {
+					Ok(x) => match expand_katakana(&x) {
+						Some(x) => {
+							let (x, devoicing) = strip_devoicing_marks(&x);
+							Reading::Actual(x, devoicing)
+						},
+						None => Reading::Error(AccentWarning {
+							reading,
+							reason: AccentWarningReason::ExpandKatakana,
+						}),
+					},
+					Err(c) => {
+						report_rejected_reading(title, &reading, c, diagnostics);
+						Reading::Error(AccentWarning { reading, reason: AccentWarningReason::Katakanify(c) })
+					},
+				},
 			);
 		}
 	}
 
-	let mut accents = ja_pron.accents;
-	let max_len = readings.len().max(accents.len());
-	readings.resize_with(max_len, || Reading::Fallback);
-	accents.resize(max_len, JaPronAccent::None);
+	let mut accents = mem::take(&mut ja_pron.accents);
+	// A template without positional readings still has one implicit reading: the title itself.
+	let reading_count = readings.len().max(1);
+	for (i, accent) in accents.iter().enumerate().skip(reading_count) {
+		if !matches!(accent, JaPronAccent::None) {
+			diagnostics.warn(title, format!("accent {} has no corresponding reading", i + 1));
+		}
+	}
+	readings.resize_with(reading_count, || Reading::Fallback);
+	accents.resize(reading_count, JaPronAccent::None);
 
 	// NOTE: Some such titles use iteration kana (いすゞ).
 	let mut last_reading = try_katakanify(title, reading_ignore, |_| false).and_then(|x| expand_katakana(&x));
 
 	let mut accent_infos = Vec::new();
 	for (i, (reading, accent)) in readings.into_iter().zip(accents).enumerate() {
-		let Some(reading) = (match reading {
-			Reading::Error => {
+		let (reading, marked_devoicing) = match reading {
+			Reading::Error(warning) => {
 				last_reading = None;
+				accent_infos.push(Err(warning));
 				continue;
 			},
-			Reading::Actual(x) => {
+			Reading::Actual(x, devoicing) => {
 				last_reading = Some(x);
-				&last_reading
+				(&last_reading, devoicing)
 			},
-			Reading::Fallback => &last_reading,
-		}) else {
+			Reading::Fallback => (&last_reading, None),
+		};
+		let Some(reading) = reading else {
 			continue;
 		};
 
-		// Ignore non-Tokyo accents.
-		if ja_pron.accent_locations.get(i).is_some_and(|x| *x) {
-			continue;
+		// Devoicing may be given either by marks in the reading or by position.
+		let duration = compute_duration(reading);
+		let mut devoicing = marked_devoicing;
+		for (_, positions) in ja_pron.devoicing.iter().filter(|(n, _)| *n == i + 1) {
+			let devoicing = devoicing.get_or_insert_with(|| vec![false; duration]);
+			for &position in positions {
+				match position.checked_sub(1).and_then(|x| devoicing.get_mut(x)) {
+					Some(mora) => *mora = true,
+					None => diagnostics
+						.warn(title, format!("devoiced mora {position} is outside reading {reading}")),
+				}
+			}
 		}
 
-		let accent = match accent {
-			JaPronAccent::Numeric(n) => Some(n),
-			JaPronAccent::Odaka => Some(compute_duration(reading).try_into().unwrap()),
-			JaPronAccent::None => None,
-		};
+		let dialect = ja_pron.dialect(i);
 
-		accent_infos.push(AccentInfo { reading: reading.clone(), accent })
-	}
+		let accent = normalize_accent(title, accent, reading, diagnostics);
+
+		let audio = ja_pron.audio.iter().filter(|(n, _)| *n == i + 1).map(|(_, file)| file.clone()).collect();
+		let qualifiers = ja_pron.qualifiers.get(i).cloned().unwrap_or_default();
+		let references = ja_pron
+			.accent_references
+			.iter()
+			.filter(|(n, _)| *n == i + 1)
+			.map(|(_, reference)| reference.clone())
+			.collect();
 
-	assert!(accent_infos.iter().all(|i| i.accent.is_none_or(|a| a as usize <= compute_duration(&i.reading))));
+		accent_infos.push(Ok(AccentInfo {
+			reading: reading.clone(),
+			accent,
+			audio,
+			qualifiers,
+			references,
+			dialect,
+			devoicing,
+			is_yougen: ja_pron.is_yougen,
+		}))
+	}
 
-	for a in &accent_infos {
+	for a in accent_infos.iter().flatten() {
 		if a.reading.chars().any(|x| matches!(x, '\u{30FD}' | '\u{30FE}')) {
-			println!("{title}, {}", a.reading);
+			diagnostics.note(title, format!("reading {} retains an iteration mark", a.reading));
 		}
 	}
 
 	accent_infos
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		diagnostics::Level,
+		parse::{parse_ja_kanjitab, parse_ja_pos, parse_ja_pron},
+	};
+
+	fn decompose(
+		title: &str, kanjitab: &str, reading: &str,
+	) -> Result<DecompositionInfo, DecompositionError> {
+		let ja_kanjitab = parse_ja_kanjitab(kanjitab).unwrap();
+		infer_decompositions(title, ja_kanjitab, &HashSet::from([reading.to_owned()]), &Diagnostics::new())
+	}
+
+	// The base, reading, and character count of each ruby atom, or the text of any other atom.
+	fn atoms(atoms: &[Atom]) -> Vec<(String, String, u8)> {
+		atoms
+			.iter()
+			.map(|atom| match atom {
+				Atom::Ruby { character_count, base, reading, .. } => {
+					(base.clone(), reading.clone(), *character_count)
+				},
+				Atom::Unknown(c) => (c.to_string(), String::new(), 0),
+				Atom::Kana(kana) => (kana.clone(), String::new(), 0),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn atoms_are_grouped_by_mode() {
+		let decomposition = decompose("図書館", "と|しょ|かん", "トショカン").unwrap();
+		let separate = [("図", "ト", 1), ("書", "ショ", 1), ("館", "カン", 1)];
+		let separate = separate.map(|(base, reading, count)| (base.to_owned(), reading.to_owned(), count));
+		assert_eq!(atoms(&decomposition.grouped(GroupMode::Separate)), separate);
+		let runs = [("図書館".to_owned(), "トショカン".to_owned(), 3)];
+		assert_eq!(atoms(&decomposition.grouped(GroupMode::Runs)), runs);
+		// Kana end a run.
+		let decomposition = decompose("取り締まり", "と|し", "トリシマリ").unwrap();
+		assert_eq!(atoms(&decomposition.grouped(GroupMode::Runs)), atoms(&decomposition.atoms));
+		// Neither do jukujikun nor omissions join a run.
+		let decomposition = decompose("今日中", "きょう2|じゅう", "キョウジュウ").unwrap();
+		assert_eq!(atoms(&decomposition.grouped(GroupMode::Runs)), atoms(&decomposition.atoms));
+		let decomposition = decompose("取締役", "と|し|やく|o1=り|o2=まり", "トリシマリヤク").unwrap();
+		assert_eq!(atoms(&decomposition.grouped(GroupMode::Runs)), atoms(&decomposition.atoms));
+	}
+
+	#[test]
+	fn accents_without_a_reading_are_dropped_with_a_warning() {
+		let diagnostics = Diagnostics::new();
+		let ja_pron = parse_ja_pron("1=とうきょう|acc3=2").unwrap();
+		let accent_infos = infer_accent("東京", ja_pron, &diagnostics);
+		assert!(matches!(&accent_infos[..], [AccentInfo { accent: None, .. }]));
+		assert_eq!(accent_infos[0].reading, "トウキョウ");
+		let warnings = diagnostics.messages_by_title(Level::Warning);
+		assert_eq!(warnings["東京"], ["accent 3 has no corresponding reading"]);
+	}
+
+	#[test]
+	fn okurigana_are_written_in_the_title_or_omitted_from_it() {
+		let decomposition = decompose("行う", "おこな", "オコナウ").unwrap();
+		assert_eq!(decomposition.to_ruby_html(), "<ruby>行<rt>オコナ</rt></ruby>ウ");
+		let decomposition = decompose("行", "おこな|o1=う", "オコナウ").unwrap();
+		assert_eq!(decomposition.reading(), "オコナウ");
+		assert_eq!(decomposition.to_ruby_html(), "<ruby>行<rt>オコナウ</rt></ruby>");
+	}
+
+	#[test]
+	fn iteration_marks_must_follow_a_kanji() {
+		assert!(matches!(decompose("々木", "き", "キキ"), Err(DecompositionError::Orphaned)));
+		assert!(matches!(decompose("あ々", "あ", "アア"), Err(DecompositionError::Orphaned)));
+		assert_eq!(decompose("人々", "ひと|びと", "ヒトビト").unwrap().reading(), "ヒトビト");
+	}
+
+	#[test]
+	fn kanjitabs_without_readings_are_reported() {
+		let diagnostics = Diagnostics::new();
+		let ja_kanjitab = parse_ja_kanjitab("k1=がみ").unwrap();
+		let result = infer_decompositions("紙", ja_kanjitab, &HashSet::new(), &diagnostics);
+		assert!(matches!(result, Err(DecompositionError::Empty)));
+		let warnings = diagnostics.messages_by_title(Level::Warning);
+		assert_eq!(warnings["紙"], ["kanjitab has alterations or omissions but no readings"]);
+	}
+
+	#[test]
+	fn kanjitab_references_are_kept_in_the_decomposition() {
+		let decomposition = decompose("雨", "あめ|kref=Daijirin", "アメ").unwrap();
+		assert_eq!(decomposition.reference.as_deref(), Some("Daijirin"));
+		assert_eq!(decompose("雨", "あめ|ref=Kojien", "アメ").unwrap().reference.as_deref(), Some("Kojien"));
+		assert_eq!(decompose("雨", "あめ", "アメ").unwrap().reference, None);
+	}
+
+	#[test]
+	fn compound_accents_follow_the_final_element() {
+		let predict = |title, kanjitab, reading, accents: &[(&str, u8)]| {
+			let accents =
+				accents.iter().map(|(reading, accent)| (reading.to_string(), vec![*accent])).collect();
+			predict_compound_accent(&decompose(title, kanjitab, reading).unwrap().atoms, &accents)
+		};
+		// A short final element takes the accent to the end of the initial one, but not onto a special mora.
+		assert_eq!(predict("大学院", "だい|がく|いん", "ダイガクイン", &[("イン", 1)]), Some(4));
+		assert_eq!(predict("日本人", "に|ほん|じん", "ニホンジン", &[("ジン", 1)]), Some(2));
+		// A medium final element is accented on its first mora unless it keeps a medial accent.
+		assert_eq!(predict("国語辞典", "こく|ご|じ|てん", "コクゴジテン", &[("ジテン", 0)]), Some(4));
+		assert_eq!(
+			predict("東京大学", "とう|きょう|だい|がく", "トウキョウダイガク", &[("ダイガク", 0)]),
+			Some(5)
+		);
+		assert_eq!(predict("総選挙", "そう|せん|きょ", "ソウセンキョ", &[("センキョ", 1)]), Some(3));
+		assert_eq!(predict("雨", "あめ", "アメ", &[("アメ", 1)]), None);
+	}
+
+	#[test]
+	fn audio_is_given_to_its_reading() {
+		let ja_pron =
+			parse_ja_pron("とうきょう|a=Ja-Tokyo.ogg|2=ときょ|audio2=Ja-Tokyo-2.ogg|acc=0").unwrap();
+		let accent_infos = infer_accent("東京", ja_pron, &Diagnostics::new());
+		let audio: Vec<_> = accent_infos.iter().map(|x| (x.reading.as_str(), x.audio.clone())).collect();
+		assert_eq!(
+			audio,
+			[("トウキョウ", vec!["Ja-Tokyo.ogg".to_owned()]), ("トキョ", vec!["Ja-Tokyo-2.ogg".to_owned()])]
+		);
+	}
+
+	#[test]
+	fn character_references_are_decoded_in_readings() {
+		let ja_pron = parse_ja_pron("と&#x30FC;きょう|acc=0").unwrap();
+		let accent_infos = infer_accent("東京", ja_pron, &Diagnostics::new());
+		assert_eq!(accent_infos[0].reading, "トーキョウ");
+	}
+
+	#[test]
+	fn accents_of_every_form_count_moras_before_the_downstep() {
+		let accents = |pron| {
+			let diagnostics = Diagnostics::new();
+			let accent_infos = infer_accent("東京", parse_ja_pron(pron).unwrap(), &diagnostics);
+			(accent_infos.iter().map(|x| x.accent).collect::<Vec<_>>(), diagnostics)
+		};
+		assert_eq!(accents("とうきょう|acc=h").0, [Some(0)]);
+		assert_eq!(accents("とうきょう|acc=a").0, [Some(1)]);
+		assert_eq!(accents("とうきょう|acc=o").0, [Some(4)]);
+		assert_eq!(accents("とうきょう|acc=2").0, [Some(2)]);
+		let (accents, diagnostics) = accents("とうきょう|acc=5");
+		assert_eq!(accents, [None]);
+		assert_eq!(
+			diagnostics.messages_by_title(Level::Warning)["東京"],
+			["accent 5 exceeds the 4 moras of reading トウキョウ"]
+		);
+	}
+
+	#[test]
+	fn empty_readings_leave_the_kanjitab_incomplete() {
+		assert!(matches!(decompose("図書館", "2|かん", "トショカン"), Err(DecompositionError::Incomplete)));
+		assert!(matches!(decompose("図書館", "と||かん", "トショカン"), Err(DecompositionError::Incomplete)));
+		assert!(decompose("図書館", "としょ2|かん", "トショカン").is_ok());
+	}
+
+	#[test]
+	fn odaka_accents_may_fall_on_a_special_mora() {
+		for (title, pron, accent) in
+			[("本", "ほん|acc=o", 2), ("珈琲", "こーひー|acc=o", 4), ("日本", "にほん|acc=3", 3)]
+		{
+			let accent_infos = infer_accent(title, parse_ja_pron(pron).unwrap(), &Diagnostics::new());
+			assert_eq!(accent_infos[0].accent, Some(accent));
+			assert_eq!(accent_infos[0].accent_type(), Some(AccentType::Odaka));
+		}
+	}
+
+	#[test]
+	fn iteration_marks_repeat_the_reading_of_their_kanji() {
+		let decomposition = decompose("人々", "ひと", "ヒトビト").unwrap();
+		assert_eq!(decomposition.reading(), "ヒトビト");
+		assert_eq!(
+			atoms(&decomposition.atoms),
+			[("人", "ヒト", 1), ("々", "ビト", 1)].map(|(base, reading, count)| (
+				base.to_owned(),
+				reading.to_owned(),
+				count
+			))
+		);
+		assert_eq!(decompose("時々", "とき", "トキドキ").unwrap().reading(), "トキドキ");
+	}
+
+	#[test]
+	fn small_ke_in_a_title_is_read_from_the_reading() {
+		let decomposition = decompose("一ヶ月", "いっ|げつ", "イッカゲツ").unwrap();
+		assert_eq!(decomposition.reading(), "イッカゲツ");
+		assert_eq!(try_katakanify("いっヶげつ", |_| false, |_| false).as_deref(), Some("イッヶゲツ"));
+	}
+
+	#[test]
+	fn yotsugana_in_the_title_match_a_merged_reading() {
+		let decomposition = decompose("手づくり", "て", "テズクリ").unwrap();
+		assert_eq!(decomposition.reading(), "テズクリ");
+		assert!(matches!(decompose("手すくり", "て", "テズクリ"), Err(DecompositionError::Mismatch)));
+	}
+
+	#[test]
+	fn ruby_html_wraps_each_ruby_atom() {
+		let decomposition = decompose("手紙", "て|かみ|k2=がみ", "テガミ").unwrap();
+		assert_eq!(decomposition.to_ruby_html(), "<ruby>手<rt>テ</rt></ruby><ruby>紙<rt>ガミ</rt></ruby>");
+		let decomposition = decompose("図書館", "としょ2|かん", "トショカン").unwrap();
+		assert_eq!(
+			decomposition.to_ruby_html(),
+			"<ruby>図書<rt>トショ</rt></ruby><ruby>館<rt>カン</rt></ruby>"
+		);
+	}
+
+	#[test]
+	fn ruby_bases_are_the_kanji_they_read() {
+		let decomposition = decompose("取り締まり", "と|し", "トリシマリ").unwrap();
+		let bases: Vec<_> = decomposition
+			.atoms
+			.iter()
+			.filter_map(|atom| match atom {
+				Atom::Ruby { base, .. } => Some(base.as_str()),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(bases, ["取", "締"]);
+		let decomposition = decompose("図書館", "としょ2|かん", "トショカン").unwrap();
+		assert_eq!(atoms(&decomposition.atoms)[0].0, "図書");
+	}
+
+	#[test]
+	fn accent_types_are_classified() {
+		let accent_type = |title, pron| {
+			infer_accent(title, parse_ja_pron(pron).unwrap(), &Diagnostics::new())[0].accent_type()
+		};
+		assert_eq!(accent_type("桜", "さくら|acc=0"), Some(AccentType::Heiban));
+		assert_eq!(accent_type("雨", "あめ|acc=1"), Some(AccentType::Atamadaka));
+		assert_eq!(accent_type("心", "こころ|acc=2"), Some(AccentType::Nakadaka));
+		assert_eq!(accent_type("男", "おとこ|acc=3"), Some(AccentType::Odaka));
+		// A one-mora word is either heiban or atamadaka.
+		assert_eq!(accent_type("木", "き|acc=1"), Some(AccentType::Atamadaka));
+		assert_eq!(accent_type("木", "き"), None);
+	}
+
+	// The sound change of each ruby atom.
+	fn sound_changes(decomposition: &DecompositionInfo) -> Vec<Option<SoundChange>> {
+		decomposition
+			.atoms
+			.iter()
+			.filter_map(|atom| match atom {
+				Atom::Ruby { sound_change, .. } => Some(*sound_change),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn altered_readings_record_their_sound_change() {
+		let decomposition = decompose("手紙", "て|かみ|k2=がみ", "テガミ").unwrap();
+		assert_eq!(sound_changes(&decomposition), [None, Some(SoundChange::Rendaku)]);
+		let decomposition = decompose("一緒", "いち|しょ|k1=いっ", "イッショ").unwrap();
+		assert_eq!(sound_changes(&decomposition), [Some(SoundChange::Sokuonbin), None]);
+		let decomposition = decompose("図書", "と|しょ", "トショ").unwrap();
+		assert_eq!(sound_changes(&decomposition), [None, None]);
+	}
+
+	#[test]
+	fn dropped_readings_give_their_reason() {
+		let diagnostics = Diagnostics::new();
+		let ja_pron = parse_ja_pron("あめ|2=ame|3=かゝゝ|acc=1").unwrap();
+		let results = infer_accent_verbose("雨", ja_pron, &diagnostics);
+		assert!(matches!(&results[0], Ok(AccentInfo { accent: Some(1), .. })));
+		assert!(matches!(
+			&results[1],
+			Err(AccentWarning { reading, reason: AccentWarningReason::Katakanify('a') }) if reading == "ame"
+		));
+		assert_eq!(
+			diagnostics.messages_by_title(Level::Warning)["雨"],
+			["reading ame rejected due to char 'a' in title 雨"]
+		);
+		// Two iteration marks cannot repeat a single kana.
+		assert!(matches!(
+			&results[2],
+			Err(AccentWarning { reading, reason: AccentWarningReason::ExpandKatakana }) if reading == "かゝゝ"
+		));
+	}
+
+	#[test]
+	fn pos_readings_are_deduplicated_in_order() {
+		let diagnostics = Diagnostics::new();
+		let ja_pos = parse_ja_pos("ja-noun", "はし|ハシ|はし|[[箸|はし]]|はじ");
+		assert_eq!(infer_pos_readings("箸", ja_pos, &diagnostics), ["ハシ", "ハジ"]);
+	}
+
+	#[test]
+	fn final_ku_and_tsu_may_geminate() {
+		let decomposition = decompose("学期", "がく|き", "ガッキ").unwrap();
+		let expected = [("学", "ガッ", 1), ("期", "キ", 1)];
+		assert_eq!(
+			atoms(&decomposition.atoms),
+			expected.map(|(base, reading, count)| (base.to_owned(), reading.to_owned(), count))
+		);
+		assert_eq!(sound_changes(&decomposition), [Some(SoundChange::Sokuonbin), None]);
+		assert_eq!(decompose("実際", "じつ|さい", "ジッサイ").unwrap().reading(), "ジッサイ");
+		assert!(matches!(decompose("学期", "がく|き", "ガッチ"), Err(DecompositionError::Mismatch)));
+	}
+
+	#[test]
+	fn devoicing_may_be_marked_or_given_by_position() {
+		let devoicing = |pron| {
+			let diagnostics = Diagnostics::new();
+			let accent_infos = infer_accent("北", parse_ja_pron(pron).unwrap(), &diagnostics);
+			(accent_infos[0].reading.clone(), accent_infos[0].devoicing.clone(), diagnostics)
+		};
+		let (reading, marks, _) = devoicing("_きた|acc=1");
+		assert_eq!((reading.as_str(), marks), ("キタ", Some(vec![true, false])));
+		let (reading, marks, _) = devoicing("きた|dev=1|acc=1");
+		assert_eq!((reading.as_str(), marks), ("キタ", Some(vec![true, false])));
+		assert_eq!(devoicing("きた|acc=1").1, None);
+		let (_, marks, diagnostics) = devoicing("きた|dev=3|acc=1");
+		assert_eq!(marks, Some(vec![false, false]));
+		assert_eq!(
+			diagnostics.messages_by_title(Level::Warning)["北"],
+			["devoiced mora 3 is outside reading キタ"]
+		);
+	}
+
+	#[test]
+	fn conjugable_words_are_flagged_on_each_accent() {
+		let accent_infos =
+			infer_accent("食べる", parse_ja_pron("たべる|y=1|acc=2").unwrap(), &Diagnostics::new());
+		assert!(accent_infos.iter().all(|x| x.is_yougen));
+	}
+
+	#[test]
+	fn kanjitab_yomi_is_kept_in_the_decomposition() {
+		let decomposition = decompose("湯桶", "ゆ|とう|yomi=yutou", "ユトウ").unwrap();
+		assert_eq!(decomposition.yomi, Some(Yomi::Yutou));
+	}
+
+	#[test]
+	fn omissions_must_follow_a_kanji() {
+		let decomposition = decompose("取締役", "と|し|やく|o1=り|o2=まり", "トリシマリヤク").unwrap();
+		// Kana omitted from the title are read with the kanji they follow.
+		assert_eq!(
+			decomposition.to_ruby_html(),
+			"<ruby>取<rt>トリ</rt></ruby><ruby>締<rt>シマリ</rt></ruby><ruby>役<rt>ヤク</rt></ruby>"
+		);
+		assert!(matches!(
+			decompose("取締役", "と|し|やく|o1=り|o4=まり", "トリシマリヤク"),
+			Err(DecompositionError::MisplacedOmission)
+		));
+	}
+
+	#[test]
+	fn readings_may_not_span_past_the_kanji() {
+		assert!(matches!(decompose("手", "てがみ2", "テガミ"), Err(DecompositionError::Mismatch)));
+		assert!(matches!(decompose("手がみ", "てがみ2", "テガミ"), Err(DecompositionError::Mismatch)));
+	}
+
+	#[test]
+	fn empty_locations_are_tokyo() {
+		assert_eq!(Dialect::from_location(""), Dialect::Tokyo);
+		assert_eq!(Dialect::from_location(" "), Dialect::Tokyo);
+		assert_eq!(Dialect::from_location(" 大阪 "), Dialect::Keihan);
+		assert_eq!(Dialect::from_location("Hokkaido"), Dialect::Other("Hokkaido".to_owned()));
+	}
+}
@@ -1,4 +1,7 @@
-use std::ops::RangeInclusive;
+use std::{
+	borrow::Cow,
+	ops::{Range, RangeInclusive},
+};
 
 pub fn is_ideograph(c: char) -> bool {
 	use std::ops::RangeInclusive;
@@ -17,6 +20,10 @@ pub fn is_ideograph(c: char) -> bool {
 		|| c == '\u{3005}'
 }
 
+pub fn count_ideographs(s: &str) -> usize {
+	s.chars().filter(|c| is_ideograph(*c)).count()
+}
+
 const HIRA_0: RangeInclusive<char> = '\u{3041}'..='\u{3096}';
 const HIRA_1: RangeInclusive<char> = '\u{309D}'..='\u{309F}';
 const KATA_0: RangeInclusive<char> = '\u{30A1}'..='\u{30FA}';
@@ -29,18 +36,31 @@ const HIRA_YE: char = '\u{1B001}';
 const KATA_YE: char = '\u{1B121}';
 const COMBINING_SOUND_MARK: RangeInclusive<char> = '\u{3099}'..='\u{309A}';
 
-pub fn try_consume_kana(c: char, chars: &mut std::str::Chars) -> Option<String> {
-	if HIRA_0.contains(&c)
+pub fn is_hiragana(c: char) -> bool {
+	HIRA_0.contains(&c)
 		|| HIRA_1.contains(&c)
-		|| KATA_0.contains(&c)
-		|| KATA_1.contains(&c)
 		|| SMALL_HIRA_KO == c
-		|| SMALL_KATA_KO == c
 		|| SMALL_HIRA_WIEO.contains(&c)
-		|| SMALL_KATA_WIEO.contains(&c)
 		|| HIRA_YE == c
+}
+
+// NOTE: This includes the prolonged sound mark ー.
+pub fn is_katakana(c: char) -> bool {
+	KATA_0.contains(&c)
+		|| KATA_1.contains(&c)
+		|| SMALL_KATA_KO == c
+		|| SMALL_KATA_WIEO.contains(&c)
 		|| KATA_YE == c
-	{
+}
+
+pub fn is_kana(c: char) -> bool {
+	is_hiragana(c) || is_katakana(c)
+}
+
+pub fn try_consume_kana(c: char, chars: &mut std::str::Chars) -> Option<String> {
+	if let Some(kana) = try_consume_half_width(c, chars) {
+		Some(kana)
+	} else if is_kana(c) {
 		let mut buffer = c.to_string();
 		buffer.extend(chars.clone().take_while(|x| COMBINING_SOUND_MARK.contains(x)).inspect(|_| {
 			chars.next();
@@ -51,18 +71,88 @@ pub fn try_consume_kana(c: char, chars: &mut std::str::Chars) -> Option<String>
 	}
 }
 
+// The full-width forms of the half-width characters from U+FF61 to U+FF9D.
+const HALF_WIDTH: [char; 61] = [
+	'。', '「', '」', '、', '・', 'ヲ', 'ァ', 'ィ', 'ゥ', 'ェ', 'ォ', 'ャ', 'ュ', 'ョ', 'ッ', 'ー', 'ア',
+	'イ', 'ウ', 'エ', 'オ', 'カ', 'キ', 'ク', 'ケ', 'コ', 'サ', 'シ', 'ス', 'セ', 'ソ', 'タ', 'チ', 'ツ',
+	'テ', 'ト', 'ナ', 'ニ', 'ヌ', 'ネ', 'ノ', 'ハ', 'ヒ', 'フ', 'ヘ', 'ホ', 'マ', 'ミ', 'ム', 'メ', 'モ',
+	'ヤ', 'ユ', 'ヨ', 'ラ', 'リ', 'ル', 'レ', 'ロ', 'ワ', 'ン',
+];
+
+// Convert a half-width character to full-width, composing it with any following half-width sound mark.
+// A sound mark that does not compose is kept as a combining mark.
+fn try_consume_half_width(c: char, chars: &mut std::str::Chars) -> Option<String> {
+	let base = *HALF_WIDTH.get((c as u32).checked_sub(0xFF61)? as usize)?;
+	let mark = match chars.clone().next() {
+		Some('\u{FF9E}') => '\u{3099}',
+		Some('\u{FF9F}') => '\u{309A}',
+		_ => return Some(base.to_string()),
+	};
+	chars.next();
+	Some(match compose_sound_mark(base, mark) {
+		Some(composed) => composed.to_string(),
+		None => format!("{base}{mark}"),
+	})
+}
+
+// Compose a katakana with a following combining dakuten or handakuten, if a precomposed form exists.
+fn compose_sound_mark(base: char, mark: char) -> Option<char> {
+	match mark {
+		'\u{3099}' if ('\u{30A1}'..='\u{30FA}').contains(&base) => {
+			reiterate_dakuon(&base.to_string()).filter(|x| *x != base)
+		},
+		'\u{309A}' if matches!(base, 'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ') => {
+			char::from_u32(base as u32 + 2)
+		},
+		_ => None,
+	}
+}
+
+// Convert the half-width characters of a string to full-width.
+fn fold_half_width(s: &str) -> Cow<'_, str> {
+	if !s.chars().any(|c| ('\u{FF61}'..='\u{FF9F}').contains(&c)) {
+		return Cow::Borrowed(s);
+	}
+	let mut buffer = String::with_capacity(s.len());
+	let mut chars = s.chars();
+	while let Some(c) = chars.next() {
+		match try_consume_half_width(c, &mut chars) {
+			Some(kana) => buffer.push_str(&kana),
+			None => buffer.push(c),
+		}
+	}
+	Cow::Owned(buffer)
+}
+
 // Attempt to normalize a reading to katakana or ….
 pub fn try_katakanify(
 	reading: &str, should_ignore: impl Fn(char) -> bool, should_keep: impl Fn(char) -> bool,
 ) -> Option<String> {
+	try_katakanify_report(reading, should_ignore, should_keep).ok()
+}
+
+// Attempt to normalize a reading to katakana, returning the first offending character on failure.
+pub fn try_katakanify_report(
+	reading: &str, should_ignore: impl Fn(char) -> bool, should_keep: impl Fn(char) -> bool,
+) -> Result<String, char> {
+	let reading = fold_half_width(reading);
 	let mut normalized = String::with_capacity(reading.len());
 	for c in reading.chars() {
+		if matches!(c, '\u{3099}' | '\u{309A}')
+			&& let Some(composed) = normalized.chars().next_back().and_then(|x| compose_sound_mark(x, c))
+		{
+			normalized.pop();
+			normalized.push(composed);
+			continue;
+		}
 		let c = match c {
 			_ if should_ignore(c) => continue,
 			_ if should_keep(c) => c,
 			// NOTE: 江 has reading containing 𛀁.
 			// NOTE: れ゚ has a combining diacritic.
-			'\u{30A1}'..='\u{30FA}' | '\u{309A}'..='\u{309C}' | '\u{30FC}' | '\u{1B164}'..='\u{1B167}' => c,
+			// NOTE: Combining marks are kept like in try_consume_kana, if they do not compose.
+			// NOTE: ヵ and ヶ are kept, as they are resolved during alignment (e.g. 一ヶ月).
+			'\u{30A1}'..='\u{30FA}' | '\u{3099}'..='\u{309C}' | '\u{30FC}' | '\u{1B164}'..='\u{1B167}' => c,
 			'\u{1B121}' | '\u{1B001}' => '\u{1B121}',
 			'\u{1B132}' | '\u{1B155}' => '\u{1B155}',
 			hira @ '\u{3041}'..='\u{3096}' => unsafe {
@@ -74,22 +164,43 @@ pub fn try_katakanify(
 			// '\u{309D}' | '\u{30FD}' | '\u{309E}' | '\u{30FE}' => panic!("{reading}: {c}"),
 			'\u{309D}' | '\u{30FD}' => '\u{30FD}',
 			'\u{309E}' | '\u{30FE}' => '\u{30FE}',
-			_ => return None,
+			_ => return Err(c),
 		};
 		normalized.push(c);
 	}
-	Some(normalized)
+	Ok(normalized)
+}
+
+// Convert katakana to hiragana, leaving anything without a hiragana counterpart (e.g. ー and ヷ) as is.
+// This is the inverse of try_katakanify for the syllabary, including the small kana extensions.
+pub fn to_hiragana(kata_string: &str) -> String {
+	kata_string
+		.chars()
+		.map(|c| match c {
+			'\u{30A1}'..='\u{30F6}' | '\u{30FD}' | '\u{30FE}' => unsafe {
+				char::from_u32_unchecked((c as u32).unchecked_sub(0x60))
+			},
+			'\u{1B164}'..='\u{1B166}' => unsafe { char::from_u32_unchecked((c as u32).unchecked_sub(0x14)) },
+			'\u{1B121}' => '\u{1B001}',
+			'\u{1B155}' => '\u{1B132}',
+			_ => c,
+		})
+		.collect()
 }
 
+// Expand the iteration marks (e.g. ヽ and ゞ) of a reading into the kana they repeat. Only katakana can be
+// repeated, so callers must katakanify a reading first, and hiragana before a mark (e.g. in すゞ) give None.
 pub fn expand_katakana(reading: &str) -> Option<String> {
 	let mut chars = reading.chars();
 	let mut kata_buffer = Vec::new();
 	let mut buffer = String::new();
 	while let Some(c) = chars.next() {
 		if let Some(kana) = try_consume_kana(c, &mut chars) {
-			match kana.as_str() {
+			match katakanify_iteration_mark(&kana) {
+				// NOTE: An iteration mark with nothing to repeat is likely a source error, and is kept as is.
+				"\u{30FD}" | "\u{30FE}" if kata_buffer.is_empty() => buffer.push_str(&kana),
 				"\u{30FD}" | "\u{30FE}" => {
-					let extra = reiterate(&kana, &kata_buffer, &mut chars)?;
+					let extra = reiterate(katakanify_iteration_mark(&kana), &kata_buffer, &mut chars)?;
 					for kata in &kata_buffer {
 						buffer.push_str(kata);
 					}
@@ -101,10 +212,12 @@ pub fn expand_katakana(reading: &str) -> Option<String> {
 				_ => kata_buffer.push(kana),
 			}
 		} else {
+			// NOTE: Kept characters (e.g. spacing sound marks) are not kana, but must survive expansion.
 			for kata in &kata_buffer {
 				buffer.push_str(kata);
 			}
 			kata_buffer.truncate(0);
+			buffer.push(c);
 		}
 	}
 	for kata in &kata_buffer {
@@ -113,13 +226,23 @@ pub fn expand_katakana(reading: &str) -> Option<String> {
 	Some(buffer)
 }
 
+// Map a hiragana iteration mark to the katakana one, as in try_katakanify.
+// NOTE: A reading may reach expansion without being katakanified as a whole (e.g. ミスゞ).
+fn katakanify_iteration_mark(kana: &str) -> &str {
+	match kana {
+		"\u{309D}" => "\u{30FD}",
+		"\u{309E}" => "\u{30FE}",
+		_ => kana,
+	}
+}
+
 // Parse a presumed nonempty sequence of kana iteration marks and attempt to normalize them into a buffer.
 fn reiterate(first: &str, source: &[String], chars: &mut std::str::Chars) -> Option<Vec<char>> {
 	let mut should_dakuten_by_mark = vec![matches!(first, "\u{30FE}")];
 	let mut peek = chars.clone();
 	while let Some(x) = peek.next() {
 		if let Some(x) = try_consume_kana(x, &mut peek) {
-			match x.as_str() {
+			match katakanify_iteration_mark(&x) {
 				"\u{30FD}" => should_dakuten_by_mark.push(false),
 				"\u{30FE}" => should_dakuten_by_mark.push(true),
 				_ => break,
@@ -133,6 +256,10 @@ fn reiterate(first: &str, source: &[String], chars: &mut std::str::Chars) -> Opt
 		return None;
 	}
 	let source = &source[source.len() - iteration_count..];
+	// NOTE: Only katakana can be reproduced, so a reading must be katakanified before expansion.
+	if !source.iter().all(|x| x.chars().next().is_some_and(|x| KATA_0.contains(&x))) {
+		return None;
+	}
 	let mut target = Vec::with_capacity(iteration_count);
 	for (original, should_dakuten) in source.iter().zip(should_dakuten_by_mark) {
 		if should_dakuten {
@@ -180,6 +307,13 @@ fn reiterate_seion(last: &str) -> char {
 	}
 }
 
+// Voice the first kana of a string of presumed katakana (e.g. for rendaku), if it can be voiced.
+pub fn voice(kata_string: &str) -> Option<String> {
+	let first = kata_string.chars().next().filter(|c| ('\u{30A1}'..='\u{30FA}').contains(c))?;
+	let voiced = reiterate_dakuon(&kata_string[..first.len_utf8()])?;
+	(voiced != first).then(|| format!("{voiced}{}", &kata_string[first.len_utf8()..]))
+}
+
 // Reproduce the given presumed katakana, attempting to attach a dakuten.
 fn reiterate_dakuon(last: &str) -> Option<char> {
 	let last = last.chars().next().unwrap();
@@ -201,7 +335,7 @@ fn reiterate_dakuon(last: &str) -> Option<char> {
 			}
 		},
 		'\u{30CF}'..='\u{30DD}' => unsafe {
-			char::from_u32_unchecked((last as u32).unchecked_add(2 - ((last as u32 - 0x30CF) % 3)))
+			char::from_u32_unchecked((last as u32).unchecked_add(1).unchecked_sub((last as u32 - 0x30CF) % 3))
 		},
 		'\u{30C4}'..='\u{30C9}' => {
 			if !(last as u32).is_multiple_of(2) {
@@ -216,12 +350,11 @@ fn reiterate_dakuon(last: &str) -> Option<char> {
 	})
 }
 
-// Compute the length, in moras, of a string of presumed katakana.
-pub fn compute_duration(kata_string: &str) -> usize {
-	let mut duration = 0;
-	for kata in kata_string.chars() {
-		match kata {
-			'\u{30A1}'
+// Whether a presumed katakana forms a mora together with the preceding one.
+fn is_dependent_kana(kata: char) -> bool {
+	matches!(
+		kata,
+		'\u{30A1}'
 			| '\u{30A3}'
 			| '\u{30A5}'
 			| '\u{30A7}'
@@ -230,9 +363,628 @@ pub fn compute_duration(kata_string: &str) -> usize {
 			| '\u{30E5}'
 			| '\u{30E7}'
 			| '\u{30EE}'
-			| '\u{1B164}'..='\u{1B166}' => continue,
-			_ => duration += 1,
+			| '\u{1B164}'..='\u{1B166}'
+	)
+}
+
+// Whether a presumed katakana can take a following small kana to form a digraph (e.g. ヴ in ヴァ).
+fn is_base_kana(kata: char) -> bool {
+	matches!(kata, '\u{30A2}'..='\u{30FA}' | '\u{1B121}')
+		&& !is_dependent_kana(kata)
+		&& !matches!(kata, '\u{30C3}' | '\u{30F3}' | '\u{30F5}' | '\u{30F6}')
+}
+
+// Merge ヂ and ヅ into ジ and ズ (and likewise for hiragana), as they are now pronounced the same.
+pub fn merge_yotsugana(s: &str) -> Cow<'_, str> {
+	if !s.contains(['ヂ', 'ヅ', 'ぢ', 'づ']) {
+		return Cow::Borrowed(s);
+	}
+	Cow::Owned(
+		s.chars()
+			.map(|c| match c {
+				'ヂ' => 'ジ',
+				'ヅ' => 'ズ',
+				'ぢ' => 'じ',
+				'づ' => 'ず',
+				_ => c,
+			})
+			.collect(),
+	)
+}
+
+// Compute the length, in moras, of a string of presumed katakana.
+pub fn compute_duration(kata_string: &str) -> usize {
+	segment_moras(kata_string).len()
+}
+
+// A mora of a string of presumed katakana: a kana and any small kana that depend on it.
+#[derive(Debug, Clone)]
+pub struct Mora<'a> {
+	pub text: &'a str,
+	pub byte_range: Range<usize>,
+}
+
+// Segment a string of presumed katakana into moras.
+pub fn segment_moras(kata_string: &str) -> Vec<Mora<'_>> {
+	let mut moras = Vec::new();
+	let mut start = 0;
+	let mut last = None;
+	for (i, kata) in kata_string.char_indices() {
+		// NOTE: An uncomposed sound mark (e.g. U+3099 after ア) belongs to the mora of the kana it marks.
+		if COMBINING_SOUND_MARK.contains(&kata) {
+			continue;
+		}
+		// NOTE: A small kana without a base (e.g. after ッ, ン, ー, or another small kana) is its own mora.
+		if i > start && !(is_dependent_kana(kata) && last.is_some_and(is_base_kana)) {
+			moras.push(Mora { text: &kata_string[start..i], byte_range: start..i });
+			start = i;
+		}
+		last = Some(kata);
+	}
+	if start < kata_string.len() {
+		moras.push(Mora { text: &kata_string[start..], byte_range: start..kata_string.len() });
+	}
+	moras
+}
+
+// Split a string of presumed katakana into moras.
+pub fn split_moras(kata_string: &str) -> Vec<&str> {
+	segment_moras(kata_string).into_iter().map(|mora| mora.text).collect()
+}
+
+// Describe the pitch of each mora of a reading in Tokyo Japanese, as H (high) or L (low), given the number
+// of moras before its downstep (0 if heiban). For example, ハシ with accent 2 is LH.
+pub fn accent_pattern(kata_string: &str, accent: u8) -> String {
+	let accent = accent as usize;
+	(1..=segment_moras(kata_string).len())
+		.map(|i| match accent {
+			0 => i > 1,
+			1 => i == 1,
+			_ => i > 1 && i <= accent,
+		})
+		.map(|is_high| if is_high { 'H' } else { 'L' })
+		.collect()
+}
+
+// Like accent_pattern, but also describes the pitch of a following particle, which distinguishes odaka from
+// heiban (e.g. ハシ with accent 2 is LHL, but with accent 0 is LHH).
+pub fn accent_pattern_with_particle(kata_string: &str, accent: u8) -> String {
+	let mut pattern = accent_pattern(kata_string, accent);
+	pattern.push(if accent == 0 { 'H' } else { 'L' });
+	pattern
+}
+
+// Guess which moras of a string of presumed katakana have a devoiced vowel in Tokyo Japanese.
+// A mora is taken to be devoiced if it has a high vowel (i or u) after a voiceless consonant, and is either
+// followed by a mora with a voiceless consonant or at the end of the reading. So as to not devoice every mora
+// of a run (e.g. キクチ), a mora directly after a devoiced mora is never devoiced.
+pub fn devoiced_moras(kata_string: &str) -> Vec<bool> {
+	let moras = segment_moras(kata_string);
+	let mut devoiced = Vec::with_capacity(moras.len());
+	for (i, mora) in moras.iter().enumerate() {
+		let is_devoiced = !devoiced.last().is_some_and(|x| *x)
+			&& DEVOICEABLE_MORAS.contains(&mora.text)
+			&& moras.get(i + 1).is_none_or(|next| next.text.starts_with(is_voiceless_onset));
+		devoiced.push(is_devoiced);
+	}
+	devoiced
+}
+
+// The moras with a high vowel after a voiceless consonant.
+const DEVOICEABLE_MORAS: [&str; 15] =
+	["キ", "ク", "シ", "ス", "チ", "ツ", "ヒ", "フ", "ピ", "プ", "キュ", "シュ", "チュ", "ヒュ", "ピュ"];
+
+// Whether a presumed katakana begins a mora with a voiceless consonant or is a geminate mark.
+fn is_voiceless_onset(kata: char) -> bool {
+	"カキクケコサシスセソタチツテトハヒフヘホパピプペポッ".contains(kata)
+}
+
+// Split a string of presumed katakana into syllables, where a long vowel mark, moraic nasal, or geminate
+// mark forms a heavy syllable with the preceding mora (e.g. コーヒー is コー|ヒー).
+pub fn split_syllables(kata_string: &str) -> Vec<&str> {
+	let mut syllables: Vec<&str> = Vec::new();
+	let mut start = 0;
+	for mora in split_moras(kata_string) {
+		let end = start + mora.len();
+		match syllables.last_mut() {
+			Some(syllable) if matches!(mora, "ー" | "ン" | "ッ") => {
+				*syllable = &kata_string[end - mora.len() - syllable.len()..end];
+			},
+			_ => syllables.push(mora),
+		}
+		start = end;
+	}
+	syllables
+}
+
+// A system of romanization.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RomajiSystem {
+	Hepburn, // Modified Hepburn (shi, tsu, ji), with macrons for long vowels.
+	Kunrei,  // Kunrei-shiki (si, tu, zi), with circumflexes for long vowels.
+	Nihon,   // Nihon-shiki (si, tu, zi, di, du, wo), with circumflexes for long vowels.
+}
+
+// Romanize a string of presumed katakana, or return None if it contains anything else.
+// NOTE: Morpheme boundaries are unknown, so ウ after an o or u sound and オ after an o sound are always
+//       taken to lengthen the previous vowel (e.g. トウキョウ is tōkyō, not toukyou).
+pub fn to_romaji(kata_string: &str, system: RomajiSystem) -> Option<String> {
+	let mut romaji = String::with_capacity(kata_string.len());
+	let mut is_geminate = false;
+	let mut is_after_nasal = false;
+	for mora in split_moras(kata_string) {
+		match mora {
+			"ー" => {
+				lengthen_vowel(&mut romaji, system)?;
+				continue;
+			},
+			"ッ" if !is_geminate => {
+				is_geminate = true;
+				continue;
+			},
+			"ン" if !is_geminate => {
+				romaji.push('n');
+				is_after_nasal = true;
+				continue;
+			},
+			_ => (),
+		}
+		let syllable = romanize_mora(mora, system)?;
+		if is_geminate {
+			// NOTE: Hepburn doubles ch as tch (e.g. マッチ is matchi).
+			let consonant = syllable.chars().next().filter(|x| !"aeiou".contains(*x))?;
+			romaji.push(if system == RomajiSystem::Hepburn && consonant == 'c' { 't' } else { consonant });
+			is_geminate = false;
+		} else if is_after_nasal && syllable.starts_with(['a', 'i', 'u', 'e', 'o', 'y']) {
+			romaji.push('\'');
+		} else if (syllable == "u" && romaji.ends_with(['o', 'u']))
+			|| (syllable == "o" && romaji.ends_with('o'))
+		{
+			lengthen_vowel(&mut romaji, system)?;
+			continue;
 		}
+		is_after_nasal = false;
+		romaji.push_str(&syllable);
+	}
+	(!is_geminate).then_some(romaji)
+}
+
+// Romanize a string of presumed katakana (e.g. from expand_katakana) in modified Hepburn.
+pub fn to_hepburn(kata_string: &str) -> Option<String> {
+	to_romaji(kata_string, RomajiSystem::Hepburn)
+}
+
+// Romanize a string of presumed katakana in Kunrei-shiki.
+pub fn to_kunrei(kata_string: &str) -> Option<String> {
+	to_romaji(kata_string, RomajiSystem::Kunrei)
+}
+
+// Romanize a string of presumed katakana in Nihon-shiki.
+pub fn to_nihon(kata_string: &str) -> Option<String> {
+	to_romaji(kata_string, RomajiSystem::Nihon)
+}
+
+// Replace the trailing vowel of a romanization with its long form. A vowel that is already long is kept.
+fn lengthen_vowel(romaji: &mut String, system: RomajiSystem) -> Option<()> {
+	let vowel = romaji.pop()?;
+	let long = match (system, vowel) {
+		(_, 'ā' | 'ī' | 'ū' | 'ē' | 'ō' | 'â' | 'î' | 'û' | 'ê' | 'ô') => vowel,
+		(RomajiSystem::Hepburn, _) => match vowel {
+			'a' => 'ā',
+			'i' => 'ī',
+			'u' => 'ū',
+			'e' => 'ē',
+			'o' => 'ō',
+			_ => return None,
+		},
+		(RomajiSystem::Kunrei | RomajiSystem::Nihon, _) => match vowel {
+			'a' => 'â',
+			'i' => 'î',
+			'u' => 'û',
+			'e' => 'ê',
+			'o' => 'ô',
+			_ => return None,
+		},
+	};
+	romaji.push(long);
+	Some(())
+}
+
+// Romanize a single mora, i.e. a kana and any small kana that depend on it.
+fn romanize_mora(mora: &str, system: RomajiSystem) -> Option<String> {
+	let mut kana = mora.chars();
+	let (consonant, vowel) = split_kana(kana.next()?)?;
+	let Some(small) = kana.next() else {
+		return Some(spell_mora(consonant, false, vowel, system));
+	};
+	if kana.next().is_some() {
+		return None;
+	}
+	// NOTE: A small ヮ only follows ク and グ, as in the historical kwa and gwa.
+	if small == 'ヮ' {
+		return (vowel == 'u' && matches!(consonant, "k" | "g")).then(|| format!("{consonant}wa"));
+	}
+	let (_, small_vowel) = split_kana(small)?;
+	let is_yoon = matches!(small, 'ャ' | 'ュ' | 'ョ');
+	if vowel == 'i' && (is_yoon || small == 'ェ') {
+		// NOTE: シェ is she in Hepburn and sye in the others, like シャ.
+		return Some(spell_mora(consonant, true, small_vowel, system));
+	}
+	let consonant = match consonant {
+		"" if vowel == 'u' => "w".to_owned(),
+		"h" if vowel == 'u' => "f".to_owned(),
+		_ => {
+			let mut syllable = spell_mora(consonant, false, vowel, system);
+			syllable.pop();
+			syllable
+		},
+	};
+	Some(format!("{consonant}{}{small_vowel}", if is_yoon { "y" } else { "" }))
+}
+
+// Spell a mora given as its Nihon-shiki consonant and vowel in a system of romanization.
+fn spell_mora(consonant: &str, is_yoon: bool, vowel: char, system: RomajiSystem) -> String {
+	let consonant = match (system, consonant, is_yoon, vowel) {
+		(RomajiSystem::Hepburn, "s", true, _) | (RomajiSystem::Hepburn, "s", false, 'i') => "sh",
+		(RomajiSystem::Hepburn, "t", true, _) | (RomajiSystem::Hepburn, "t", false, 'i') => "ch",
+		(RomajiSystem::Hepburn, "t", false, 'u') => "ts",
+		(RomajiSystem::Hepburn, "h", false, 'u') => "f",
+		(RomajiSystem::Hepburn, "z" | "d", true, _) | (RomajiSystem::Hepburn, "z" | "d", false, 'i') => "j",
+		(RomajiSystem::Hepburn | RomajiSystem::Kunrei, "d", false, 'u') => "z",
+		(RomajiSystem::Kunrei, "d", true, _) | (RomajiSystem::Kunrei, "d", false, 'i') => "z",
+		(RomajiSystem::Hepburn | RomajiSystem::Kunrei, "w", false, 'i' | 'e' | 'o') => "",
+		_ => consonant,
+	};
+	let glide = if is_yoon && !(system == RomajiSystem::Hepburn && matches!(consonant, "sh" | "ch" | "j")) {
+		"y"
+	} else {
+		""
+	};
+	format!("{consonant}{glide}{vowel}")
+}
+
+// Split a katakana into its Nihon-shiki consonant and vowel.
+fn split_kana(kata: char) -> Option<(&'static str, char)> {
+	let vowel = |i: u32| ['a', 'i', 'u', 'e', 'o'][i as usize];
+	let kata = kata as u32;
+	Some(match kata {
+		0x30A1..=0x30AA => ("", vowel((kata - 0x30A1) / 2)),
+		0x30AB..=0x30B4 => (if kata % 2 == 1 { "k" } else { "g" }, vowel((kata - 0x30AB) / 2)),
+		0x30B5..=0x30BE => (if kata % 2 == 1 { "s" } else { "z" }, vowel((kata - 0x30B5) / 2)),
+		0x30BF..=0x30C2 => (if kata % 2 == 1 { "t" } else { "d" }, vowel((kata - 0x30BF) / 2)),
+		0x30C4..=0x30C9 => (if kata.is_multiple_of(2) { "t" } else { "d" }, vowel((kata - 0x30C4) / 2 + 2)),
+		0x30CA..=0x30CE => ("n", vowel(kata - 0x30CA)),
+		0x30CF..=0x30DD => (["h", "b", "p"][((kata - 0x30CF) % 3) as usize], vowel((kata - 0x30CF) / 3)),
+		0x30DE..=0x30E2 => ("m", vowel(kata - 0x30DE)),
+		0x30E3..=0x30E8 => ("y", vowel((kata - 0x30E3) / 2 * 2)),
+		0x30E9..=0x30ED => ("r", vowel(kata - 0x30E9)),
+		0x30EE | 0x30EF => ("w", 'a'),
+		0x30F0 => ("w", 'i'),
+		0x30F1 => ("w", 'e'),
+		0x30F2 => ("w", 'o'),
+		0x30F4 => ("v", 'u'),
+		0x30F5 => ("k", 'a'),
+		0x30F6 => ("k", 'e'),
+		0x30F7..=0x30FA => ("v", ['a', 'i', 'e', 'o'][(kata - 0x30F7) as usize]),
+		_ => return None,
+	})
+}
+
+// A mora for transcription, before any assimilation to the following mora.
+enum PhoneticMora {
+	Syllable(&'static str, &'static str, char), // An onset, any palatalization or labialization, and a vowel.
+	Long,
+	Geminate,
+	Nasal,
+}
+
+// Transcribe a string of presumed katakana in broad IPA, or return None if it contains anything else.
+// This is a broad transcription: devoicing and pitch are not marked, and ン and ッ are only assimilated
+// to the following mora. The topic particle ハ cannot be told apart from isolated readings, so it is ha.
+// NOTE: Long vowels are found as in to_romaji (e.g. トウキョウ is toːkʲoː).
+pub fn to_ipa(kata_string: &str) -> Option<String> {
+	let moras = split_moras(kata_string)
+		.into_iter()
+		.map(|mora| match mora {
+			"ー" => Some(PhoneticMora::Long),
+			"ッ" => Some(PhoneticMora::Geminate),
+			"ン" => Some(PhoneticMora::Nasal),
+			_ => transcribe_mora(mora),
+		})
+		.collect::<Option<Vec<_>>>()?;
+	let mut ipa = String::with_capacity(kata_string.len());
+	for (i, mora) in moras.iter().enumerate() {
+		let next_onset = match moras.get(i + 1) {
+			Some(PhoneticMora::Syllable(onset, ..)) => Some(*onset),
+			_ => None,
+		};
+		match *mora {
+			PhoneticMora::Syllable("", "", 'u') if ipa.ends_with(['o', 'ɯ']) => ipa.push('ː'),
+			PhoneticMora::Syllable("", "", 'o') if ipa.ends_with('o') => ipa.push('ː'),
+			PhoneticMora::Syllable(onset, palatalization, vowel) => {
+				ipa.push_str(onset);
+				ipa.push_str(palatalization);
+				ipa.push(if vowel == 'u' { 'ɯ' } else { vowel });
+			},
+			PhoneticMora::Long if ipa.ends_with('ː') => (),
+			PhoneticMora::Long => {
+				ipa.chars().next_back().filter(|x| "aiɯeo".contains(*x))?;
+				ipa.push('ː');
+			},
+			PhoneticMora::Geminate => match next_onset? {
+				"" => ipa.push('ʔ'),
+				onset => ipa.push(onset.chars().next()?),
+			},
+			PhoneticMora::Nasal => ipa.push(match next_onset.and_then(|x| x.chars().next()) {
+				Some('p' | 'b' | 'm') => 'm',
+				Some('t' | 'd' | 'n' | 'ɾ' | 'z' | 's') if next_onset != Some("tɕ") => 'n',
+				Some('ɲ' | 't' | 'ʑ') => 'ɲ',
+				Some('k' | 'ɡ') => 'ŋ',
+				_ => 'ɴ',
+			}),
+		}
+	}
+	Some(ipa)
+}
+
+// Transcribe a mora of a kana and any small kana that depend on it.
+fn transcribe_mora(mora: &str) -> Option<PhoneticMora> {
+	let mut kana = mora.chars();
+	let (consonant, vowel) = split_kana(kana.next()?)?;
+	let Some(small) = kana.next() else {
+		return Some(PhoneticMora::Syllable(ipa_onset(consonant, false, vowel), "", vowel));
+	};
+	if kana.next().is_some() {
+		return None;
+	}
+	// NOTE: A small ヮ only follows ク and グ, as in the historical kwa and gwa.
+	if small == 'ヮ' {
+		return (vowel == 'u' && matches!(consonant, "k" | "g"))
+			.then(|| PhoneticMora::Syllable(ipa_onset(consonant, false, 'a'), "ʷ", 'a'));
+	}
+	let (_, small_vowel) = split_kana(small)?;
+	let is_yoon = matches!(small, 'ャ' | 'ュ' | 'ョ');
+	Some(if vowel == 'i' && (is_yoon || small == 'ェ') {
+		PhoneticMora::Syllable(ipa_onset(consonant, true, small_vowel), "", small_vowel)
+	} else if is_yoon {
+		// NOTE: A yōon after a base other than an i sound (e.g. テュ) palatalizes the consonant as is.
+		PhoneticMora::Syllable(ipa_onset(consonant, false, vowel), "ʲ", small_vowel)
+	} else if consonant.is_empty() && vowel == 'u' {
+		PhoneticMora::Syllable("w", "", small_vowel)
+	} else {
+		PhoneticMora::Syllable(ipa_onset(consonant, false, vowel), "", small_vowel)
+	})
+}
+
+// Transcribe a Nihon-shiki consonant before a vowel, given whether it is palatalized by a yōon.
+// NOTE: ヴ is transcribed as it is usually pronounced, with b.
+fn ipa_onset(consonant: &str, is_palatal: bool, vowel: char) -> &'static str {
+	match (consonant, is_palatal, vowel) {
+		("k", false, _) => "k",
+		("k", true, _) => "kʲ",
+		("g", false, _) => "ɡ",
+		("g", true, _) => "ɡʲ",
+		("s", true, _) | ("s", false, 'i') => "ɕ",
+		("s", false, _) => "s",
+		("z" | "d", true, _) | ("z" | "d", false, 'i') => "ʑ",
+		("z", false, _) | ("d", false, 'u') => "z",
+		("d", false, _) => "d",
+		("t", true, _) | ("t", false, 'i') => "tɕ",
+		("t", false, 'u') => "ts",
+		("t", false, _) => "t",
+		("n", true, _) | ("n", false, 'i') => "ɲ",
+		("n", false, _) => "n",
+		("h", true, _) | ("h", false, 'i') => "ç",
+		("h", false, 'u') => "ɸ",
+		("h", false, _) => "h",
+		("b" | "v", false, _) => "b",
+		("b" | "v", true, _) => "bʲ",
+		("p", false, _) => "p",
+		("p", true, _) => "pʲ",
+		("m", false, _) => "m",
+		("m", true, _) => "mʲ",
+		("r", false, _) => "ɾ",
+		("r", true, _) => "ɾʲ",
+		("y", ..) => "j",
+		("w", _, 'a') => "w",
+		_ => "",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn systems_differ_in_their_consonants() {
+		assert_eq!(to_hepburn("シ").as_deref(), Some("shi"));
+		assert_eq!(to_kunrei("シ").as_deref(), Some("si"));
+		assert_eq!(to_nihon("シ").as_deref(), Some("si"));
+		assert_eq!(to_hepburn("ツヅジフ").as_deref(), Some("tsuzujifu"));
+		assert_eq!(to_kunrei("ツヅジフ").as_deref(), Some("tuzuzihu"));
+		assert_eq!(to_nihon("ツヅジフ").as_deref(), Some("tuduzihu"));
+	}
+
+	#[test]
+	fn long_vowels_are_marked() {
+		assert_eq!(to_hepburn("トウキョウ").as_deref(), Some("tōkyō"));
+		assert_eq!(to_kunrei("トウキョウ").as_deref(), Some("tôkyô"));
+		assert_eq!(to_hepburn("ガッコウ").as_deref(), Some("gakkō"));
+	}
+
+	#[test]
+	fn small_wa_follows_only_ku_and_gu() {
+		assert_eq!(to_nihon("クヮジ").as_deref(), Some("kwazi"));
+		assert_eq!(to_hepburn("グヮイ").as_deref(), Some("gwai"));
+		assert_eq!(to_kunrei("シヮ"), None);
+		assert_eq!(to_ipa("クヮ").as_deref(), Some("kʷa"));
+	}
+
+	#[test]
+	fn rejected_readings_report_the_offending_character() {
+		assert_eq!(try_katakanify_report("とうkyo", |_| false, |_| false), Err('k'));
+		assert_eq!(try_katakanify_report("とう kyo", char::is_whitespace, |_| false), Err('k'));
+		assert_eq!(try_katakanify_report("とうきょう", |_| false, |_| false).as_deref(), Ok("トウキョウ"));
+	}
+
+	#[test]
+	fn syllables_group_special_moras_with_the_preceding_mora() {
+		assert_eq!(split_moras("コーヒー"), ["コ", "ー", "ヒ", "ー"]);
+		assert_eq!(split_syllables("コーヒー"), ["コー", "ヒー"]);
+		assert_eq!(split_moras("ガッコウ"), ["ガ", "ッ", "コ", "ウ"]);
+		assert_eq!(split_syllables("ガッコウ"), ["ガッ", "コ", "ウ"]);
+		assert_eq!(split_syllables("シンブン"), ["シン", "ブン"]);
+	}
+
+	#[test]
+	fn expansion_keeps_characters_that_are_not_repeated() {
+		assert_eq!(expand_katakana("コーヒー").as_deref(), Some("コーヒー"));
+		assert_eq!(expand_katakana("キャッチ").as_deref(), Some("キャッチ"));
+		assert_eq!(expand_katakana("ア゛").as_deref(), Some("ア゛"));
+		assert_eq!(expand_katakana("ココロヽ").as_deref(), Some("ココロロ"));
+	}
+
+	#[test]
+	fn systems_differ_in_their_palatalized_and_historical_kana() {
+		assert_eq!(to_hepburn("チャヂャヲ").as_deref(), Some("chajao"));
+		assert_eq!(to_kunrei("チャヂャヲ").as_deref(), Some("tyazyao"));
+		assert_eq!(to_nihon("チャヂャヲ").as_deref(), Some("tyadyawo"));
+		assert_eq!(to_romaji("シンブン", RomajiSystem::Kunrei), to_kunrei("シンブン"));
+	}
+
+	#[test]
+	fn hiragana_round_trips_through_katakana() {
+		for hira in ["とうきょう", "ゔぁゕゖ", "こゝろ", "\u{1B132}\u{1B150}\u{1B151}\u{1B152}"] {
+			let kata = try_katakanify(hira, |_| false, |_| false).unwrap();
+			assert_eq!(to_hiragana(&kata), hira);
+		}
+		assert_eq!(to_hiragana("コーヒー・ヷ"), "こーひー・ヷ");
+	}
+
+	#[test]
+	fn hepburn_handles_special_moras() {
+		assert_eq!(to_hepburn("トーキョー").as_deref(), Some("tōkyō"));
+		assert_eq!(to_hepburn("シンイチ").as_deref(), Some("shin'ichi"));
+		assert_eq!(to_hepburn("キンヨウビ").as_deref(), Some("kin'yōbi"));
+		assert_eq!(to_hepburn("キャッチ").as_deref(), Some("kyatchi"));
+		assert_eq!(to_hepburn("シンブン").as_deref(), Some("shinbun"));
+		assert_eq!(to_hepburn("ア雨"), None);
+	}
+
+	#[test]
+	fn half_width_katakana_is_folded() {
+		let katakanify = |x| try_katakanify(x, |_| false, |_| false);
+		assert_eq!(katakanify("ﾄｳｷｮｳ").as_deref(), Some("トウキョウ"));
+		assert_eq!(katakanify("ｶﾞｯｺｳ").as_deref(), Some("ガッコウ"));
+		assert_eq!(katakanify("ﾊﾟﾝｰ").as_deref(), Some("パンー"));
+		// A sound mark that does not compose is kept as a combining mark.
+		assert_eq!(katakanify("ｱﾞ").as_deref(), Some("ア\u{3099}"));
+	}
+
+	#[test]
+	fn accent_patterns_describe_each_mora() {
+		assert_eq!(accent_pattern("ハシ", 1), "HL");
+		assert_eq!(accent_pattern("ハシ", 2), "LH");
+		assert_eq!(accent_pattern("ハシ", 0), "LH");
+		assert_eq!(accent_pattern_with_particle("ハシ", 1), "HLL");
+		assert_eq!(accent_pattern_with_particle("ハシ", 2), "LHL");
+		assert_eq!(accent_pattern_with_particle("ハシ", 0), "LHH");
+		// Moras are counted, not characters.
+		assert_eq!(accent_pattern("トウキョウ", 0), "LHHH");
+		assert_eq!(accent_pattern("キャッチャー", 1), "HLLL");
+		assert_eq!(accent_pattern("ココロザシ", 3), "LHHLL");
+	}
+
+	#[test]
+	fn combining_sound_marks_are_composed() {
+		let katakanify = |x| try_katakanify(x, |_| false, |_| false);
+		assert_eq!(katakanify("か\u{3099}き\u{3099}").as_deref(), Some("ガギ"));
+		assert_eq!(katakanify("は\u{309A}ン").as_deref(), Some("パン"));
+		assert_eq!(katakanify("ウ\u{3099}").as_deref(), Some("ヴ"));
+		assert_eq!(compute_duration(&katakanify("か\u{3099}っこう").unwrap()), 4);
+	}
+
+	#[test]
+	fn devoicing_needs_a_following_voiceless_consonant() {
+		assert_eq!(devoiced_moras("クツ"), [true, false]);
+		assert_eq!(devoiced_moras("キク"), [true, false]);
+		assert_eq!(devoiced_moras("スキ"), [true, false]);
+		assert_eq!(devoiced_moras("デス"), [false, true]);
+		assert_eq!(devoiced_moras("クモ"), [false, false]);
+		assert_eq!(devoiced_moras("シュクダイ"), [true, false, false, false]);
+	}
+
+	#[test]
+	fn uncomposed_sound_marks_stay_with_their_kana() {
+		let kata = try_katakanify("あ\u{3099}め", |_| false, |_| false).unwrap();
+		assert_eq!(kata, "ア\u{3099}メ");
+		assert_eq!(expand_katakana(&kata).as_deref(), Some("ア\u{3099}メ"));
+		assert_eq!(compute_duration(&kata), 2);
+		let mut chars = "\u{3099}メ".chars();
+		assert_eq!(try_consume_kana('ア', &mut chars).as_deref(), Some("ア\u{3099}"));
+		assert_eq!(chars.as_str(), "メ");
+	}
+
+	#[test]
+	fn kana_predicates_cover_the_extensions() {
+		assert!(is_hiragana('\u{3096}') && !is_katakana('\u{3096}'));
+		assert!(is_katakana('\u{30FA}') && !is_hiragana('\u{30FA}'));
+		assert!(is_katakana('\u{30FC}') && is_kana('\u{30FC}'));
+		assert!(is_hiragana('\u{1B132}') && is_katakana('\u{1B155}'));
+		assert!(!is_kana('\u{30FB}') && !is_kana('\u{3097}') && !is_kana('雨'));
+	}
+
+	#[test]
+	fn leading_iteration_marks_are_kept() {
+		assert_eq!(expand_katakana("\u{30FD}カ").as_deref(), Some("\u{30FD}カ"));
+		assert_eq!(expand_katakana("\u{30FE}カ").as_deref(), Some("\u{30FE}カ"));
+		assert_eq!(expand_katakana("カ\u{30FE}").as_deref(), Some("カガ"));
+	}
+
+	#[test]
+	fn moras_are_segmented_with_their_byte_ranges() {
+		let moras: Vec<_> =
+			segment_moras("キャッチャー").into_iter().map(|x| (x.text, x.byte_range)).collect();
+		assert_eq!(moras, [("キャ", 0..6), ("ッ", 6..9), ("チャ", 9..15), ("ー", 15..18)]);
+		assert_eq!(split_moras("ンャ"), ["ン", "ャ"]);
+		assert_eq!(compute_duration("キャッチャー"), 4);
+		assert!(segment_moras("").is_empty());
+	}
+
+	#[test]
+	fn hiragana_iteration_marks_are_expanded() {
+		let expand = |x| expand_katakana(&try_katakanify(x, |_| false, |_| false)?);
+		assert_eq!(expand("いすゞ").as_deref(), Some("イスズ"));
+		assert_eq!(expand("みすゞ").as_deref(), Some("ミスズ"));
+		assert_eq!(expand_katakana("すゞ"), None);
+		assert_eq!(expand("こゝろ").as_deref(), Some("ココロ"));
+		assert_eq!(expand("ゝ").as_deref(), Some("\u{30FD}"));
+	}
+
+	#[test]
+	fn small_vowels_combine_only_with_a_valid_base() {
+		assert_eq!(split_moras("ヴァイオリン"), ["ヴァ", "イ", "オ", "リ", "ン"]);
+		assert_eq!(split_moras("ファン"), ["ファ", "ン"]);
+		assert_eq!(compute_duration("ァ"), 1);
+		assert_eq!(compute_duration("ンァ"), 2);
+		assert_eq!(compute_duration("ッァ"), 2);
+	}
+
+	#[test]
+	fn yotsugana_are_merged() {
+		assert_eq!(merge_yotsugana("いづみ"), merge_yotsugana("いずみ"));
+		assert_eq!(merge_yotsugana("ハナヂ"), "ハナジ");
+		assert_eq!(merge_yotsugana("ツヅク"), "ツズク");
+		assert!(matches!(merge_yotsugana("イズミ"), Cow::Borrowed("イズミ")));
+	}
+
+	#[test]
+	fn ipa_gives_allophones_by_context() {
+		assert_eq!(to_ipa("チズ").as_deref(), Some("tɕizɯ"));
+		assert_eq!(to_ipa("キッテ").as_deref(), Some("kitte"));
+		assert_eq!(to_ipa("シンブン").as_deref(), Some("ɕimbɯɴ"));
+		assert_eq!(to_ipa("ツキ").as_deref(), Some("tsɯki"));
+		assert_eq!(to_ipa("トウキョウ").as_deref(), Some("toːkʲoː"));
+		assert_eq!(to_ipa("ー"), None);
 	}
-	duration
 }
@@ -0,0 +1,892 @@
+pub mod diagnostics;
+#[cfg(feature = "xml")]
+pub mod dump;
+pub mod infer;
+pub mod ja;
+pub mod parse;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stream;
+pub mod wikitext;
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	diagnostics::Diagnostics,
+	infer::{
+		DecompositionInfo, Dialect, infer_accent, infer_decompositions, infer_pos_readings, infer_usex_words,
+		predict_compound_accent,
+	},
+	ja::count_ideographs,
+	parse::{
+		JaKanjitab, JaPos, JaPron, Yomi, parse_ja_altread, parse_ja_compound, parse_ja_kanjitab,
+		parse_ja_pos, parse_ja_pron, parse_ja_usex,
+	},
+	wikitext::{FindTemplates, Sections, TemplateParameters},
+};
+
+// What a template handler extracts from the arguments of its template.
+pub enum ExtractContribution {
+	Pron(JaPron),
+	Kanjitab(JaKanjitab),
+	Pos(JaPos),
+	Nothing,
+}
+
+// The options for extraction, along with a registry of template handlers that take precedence over the
+// built-in ones.
+pub struct Extractor {
+	pub accent_conflicts: AccentConflictPolicy,
+	handlers: HashMap<String, fn(&str) -> ExtractContribution>,
+}
+
+impl Extractor {
+	pub fn new(accent_conflicts: AccentConflictPolicy) -> Self {
+		Self { accent_conflicts, handlers: HashMap::new() }
+	}
+
+	pub fn register(&mut self, name: impl Into<String>, handler: fn(&str) -> ExtractContribution) {
+		self.handlers.insert(name.into(), handler);
+	}
+}
+
+// How to treat a reading that is given different accents by different sources.
+#[derive(Clone, Copy)]
+pub enum AccentConflictPolicy {
+	KeepAll,
+	PreferFirst,
+	Flag,
+}
+
+// Everything extracted from the pages processed so far.
+#[derive(Default)]
+pub struct Extraction {
+	pub info: HashMap<String, WordInfo>,
+	pub redirects: Vec<Redirect>, // Redirects are resolved once every page is processed.
+	pub sentences: Vec<Sentence>,
+}
+
+// Extract what a page gives about its title, from its Japanese section.
+pub fn process_page(
+	title: &str, text: &str, extraction: &mut Extraction, extractor: &Extractor, diagnostics: &Diagnostics,
+) {
+	// Narrow text to Japanese section.
+	// NOTE: The heading may only appear in a comment, despite the dump being filtered by it.
+	let Some(section) = Sections::new(text).find(|x| x.level == 2 && x.title == "Japanese") else {
+		diagnostics.note(title, "page has no Japanese section");
+		return;
+	};
+	let text = section.body;
+
+	// Split text by etymology if multiple such sections exist, then process each subtext.
+	// NOTE: Sometimes, a text will have "Etymology 1" but only have one etymology. (e.g. 操)
+	// NOTE: Sometimes, a text will have multiple "Etymology" sections. (e.g. 薄)
+	let etymologies: Vec<&str> = Sections::new(text)
+		.filter(|x| x.level == 3 && x.title.starts_with("Etymology"))
+		.map(|x| x.body)
+		.collect();
+	if etymologies.len() > 1 {
+		for (i, text) in etymologies.into_iter().enumerate() {
+			let etymology = u8::try_from(i + 1).ok();
+			process(title, text, etymology, extraction, extractor, diagnostics);
+		}
+	} else {
+		process(title, text, None, extraction, extractor, diagnostics);
+	}
+}
+
+// Extract the information of the given pages, as titles and texts, and resolve their redirects.
+// NOTE: This uses the default extractor and discards all diagnostics; see process_page otherwise.
+pub fn build_info(records: impl Iterator<Item = (String, String)>) -> HashMap<String, WordInfo> {
+	let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+	let diagnostics = Diagnostics::new();
+	let mut extraction = Extraction::default();
+	for (title, text) in records {
+		process_page(&title, &text, &mut extraction, &extractor, &diagnostics);
+	}
+	resolve_redirects(extraction.redirects, &mut extraction.info, &diagnostics);
+	extraction.info
+}
+
+// Give each redirect the decompositions of its kanjitabs that match the readings of the title it sees.
+// A redirect may see another redirect (e.g. A sees B, which sees C), so each waits, as in StreamingWriter,
+// until the first title it sees that has information or is a redirect is resolved.
+// NOTE: This only depends on the readings of non-redirect titles, so it may be run apart from the read loop
+//       on a serialized extraction (see the resolve-redirects subcommand).
+pub fn resolve_redirects(
+	redirects: Vec<Redirect>, info: &mut HashMap<String, WordInfo>, diagnostics: &Diagnostics,
+) {
+	let resolve = |redirect: Redirect, info: &mut HashMap<String, WordInfo>| {
+		if let Some(readings) = redirect.sees.iter().find_map(|see| info.get(see)).map(|x| x.readings.clone())
+		{
+			resolve_redirect(redirect, &readings, info, diagnostics);
+		}
+	};
+	let mut pending = redirects;
+	while !pending.is_empty() {
+		let unresolved: HashSet<String> = pending.iter().map(|x| x.title.clone()).collect();
+		let (ready, blocked): (Vec<_>, Vec<_>) = pending.into_iter().partition(|redirect| {
+			!redirect
+				.sees
+				.iter()
+				.find(|see| unresolved.contains(*see) || info.contains_key(*see))
+				.is_some_and(|see| unresolved.contains(see))
+		});
+		if ready.is_empty() {
+			// The remaining redirects wait on a cycle, so each takes the first title it sees with information.
+			for redirect in blocked {
+				resolve(redirect, info);
+			}
+			return;
+		}
+		for redirect in ready {
+			resolve(redirect, info);
+		}
+		pending = blocked;
+	}
+}
+
+// Give a redirect the decompositions of its kanjitabs that match the given readings of the title it sees.
+pub(crate) fn resolve_redirect(
+	redirect: Redirect, readings: &HashSet<String>, info: &mut HashMap<String, WordInfo>,
+	diagnostics: &Diagnostics,
+) {
+	// The redirect takes the readings it sees, for any redirects to it.
+	let new_info = info.entry(redirect.title.clone()).or_insert_with(|| WordInfo::new(&redirect.title));
+	new_info.readings.extend(readings.iter().cloned());
+	for ja_kanjitab in redirect.ja_kanjitabs {
+		let Some(decomposition) = decompose(&redirect.title, ja_kanjitab, readings, diagnostics) else {
+			continue;
+		};
+		let reading = decomposition.reading();
+		let reading_info = new_info.reading_infos.entry(reading).or_default();
+		// NOTE: Assumes duplicate decompositions (e.g. 綽約) are identical.
+		if reading_info.decomposition.is_none() {
+			reading_info.decomposition = Some(decomposition);
+		}
+	}
+}
+
+// Predict the accents of decomposed readings without a known accent from the accents of their components.
+pub fn predict_accents(info: &mut HashMap<String, WordInfo>) {
+	let mut component_accents: HashMap<String, Vec<u8>> = HashMap::new();
+	for word_info in info.values() {
+		for (reading, reading_info) in &word_info.reading_infos {
+			component_accents.entry(reading.clone()).or_default().extend(&reading_info.accents);
+		}
+	}
+	component_accents.retain(|_, accents| !accents.is_empty());
+	for word_info in info.values_mut() {
+		for reading_info in word_info.reading_infos.values_mut() {
+			if reading_info.accents.is_empty()
+				&& let Some(decomposition) = &reading_info.decomposition
+			{
+				reading_info.predicted_accent =
+					predict_compound_accent(&decomposition.atoms, &component_accents);
+			}
+		}
+	}
+}
+
+// Sort and deduplicate the accents of each reading, once every page is processed.
+pub fn finalize_accents(info: &mut HashMap<String, WordInfo>) {
+	for word_info in info.values_mut() {
+		word_info.finalize_accents();
+	}
+}
+
+// Discard readings for which nothing was inferred, and then titles without any readings.
+pub fn remove_empty_readings(info: &mut HashMap<String, WordInfo>, diagnostics: &Diagnostics) {
+	info.retain(|_, word_info| word_info.remove_empty_readings(diagnostics));
+}
+
+// The readings taken by a kanji with their frequencies, split into on'yomi and kun'yomi where the yomi of the
+// kanjitab gives the class of each reading.
+#[derive(Default)]
+pub struct KanjiReadings {
+	pub on: HashMap<String, u32>,
+	pub kun: HashMap<String, u32>,
+	pub unclassified: HashMap<String, u32>,
+}
+
+// Tally the readings taken by each kanji across all decompositions.
+pub fn aggregate_kanji_readings(info: &HashMap<String, WordInfo>) -> HashMap<char, KanjiReadings> {
+	let mut kanji_readings: HashMap<char, KanjiReadings> = HashMap::new();
+	for word_info in info.values() {
+		for reading_info in word_info.reading_infos.values() {
+			let Some(decomposition) = &reading_info.decomposition else { continue };
+			let readings = decomposition.kanji_readings();
+			let count = readings.len();
+			for (i, (kanji, reading)) in readings.into_iter().enumerate() {
+				let readings = kanji_readings.entry(kanji).or_default();
+				let readings = match decomposition.yomi.and_then(|x| x.of_kanji(i, count)) {
+					Some(Yomi::On) => &mut readings.on,
+					Some(Yomi::Kun) => &mut readings.kun,
+					_ => &mut readings.unclassified,
+				};
+				*readings.entry(reading.to_owned()).or_default() += 1;
+			}
+		}
+	}
+	kanji_readings
+}
+
+// Index the titles by their readings, sorted by reading and then by title.
+pub fn index_readings(info: &HashMap<String, WordInfo>) -> Vec<(String, Vec<String>)> {
+	let mut index: HashMap<&str, Vec<String>> = HashMap::new();
+	for (title, word_info) in info {
+		for reading in word_info.reading_infos.keys() {
+			index.entry(reading).or_default().push(title.clone());
+		}
+	}
+	let mut index: Vec<_> = index
+		.into_iter()
+		.map(|(reading, mut titles)| {
+			titles.sort_unstable();
+			(reading.to_owned(), titles)
+		})
+		.collect();
+	index.sort_unstable();
+	index
+}
+
+// Find readings shared by multiple titles with at least two distinct accents between them, sorted by
+// reading, and pair each with its titles and their accents.
+pub fn find_minimal_pairs(info: &HashMap<String, WordInfo>) -> Vec<(String, Vec<(String, u8)>)> {
+	let mut groups: HashMap<&str, Vec<(String, u8)>> = HashMap::new();
+	for (title, word_info) in info {
+		for (reading, reading_info) in &word_info.reading_infos {
+			let mut accents = reading_info.accents.clone();
+			accents.sort_unstable();
+			accents.dedup();
+			let group = groups.entry(reading).or_default();
+			group.extend(accents.into_iter().map(|accent| (title.clone(), accent)));
+		}
+	}
+	let mut minimal_pairs: Vec<_> = groups
+		.into_iter()
+		.filter(|(_, group)| {
+			group.iter().any(|(title, accent)| group.iter().any(|(t, a)| t != title && a != accent))
+		})
+		.map(|(reading, mut group)| {
+			group.sort_unstable();
+			(reading.to_owned(), group)
+		})
+		.collect();
+	minimal_pairs.sort_unstable();
+	minimal_pairs
+}
+
+// A title whose kanjitabs are only decomposed by the readings of the titles it sees.
+#[derive(Serialize, Deserialize)]
+pub struct Redirect {
+	pub title: String,
+	// NOTE: There may be multiple redirects and multiple kanji tables (see 米[メートル|メーター]).
+	pub ja_kanjitabs: Vec<JaKanjitab>,
+	pub sees: Vec<String>,
+}
+
+// An example sentence from a usage-example template, split into words where possible.
+pub struct Sentence {
+	pub title: String,
+	pub text: String,
+	pub words: Vec<(String, Option<String>)>,
+	pub translation: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WordInfo {
+	pub reading_infos: HashMap<String, ReadingInfo>,
+	pub readings: HashSet<String>,
+	pub kana_only: bool,       // Whether the title has no kanji, and so needs no furigana.
+	pub warnings: Vec<String>, // The warnings reported for the title, if requested.
+}
+
+impl WordInfo {
+	pub fn new(title: &str) -> Self {
+		Self {
+			reading_infos: HashMap::new(),
+			readings: HashSet::new(),
+			kana_only: count_ideographs(title) == 0,
+			warnings: Vec::new(),
+		}
+	}
+
+	pub fn finalize_accents(&mut self) {
+		for reading_info in self.reading_infos.values_mut() {
+			reading_info.accents.sort_unstable();
+			reading_info.accents.dedup();
+			reading_info.etymology_accents.sort_unstable();
+			reading_info.etymology_accents.dedup();
+			reading_info.dialect_accents.sort_unstable();
+			reading_info.dialect_accents.dedup();
+			reading_info.pron.audio.sort_unstable();
+			reading_info.pron.audio.dedup();
+			reading_info.pron.qualifiers.sort_unstable();
+			reading_info.pron.qualifiers.dedup();
+		}
+	}
+
+	// Discard readings for which nothing was inferred, tallying each. Returns whether any reading is left, and
+	// otherwise tallies the title too.
+	pub fn remove_empty_readings(&mut self, diagnostics: &Diagnostics) -> bool {
+		self.reading_infos.retain(|_, info| {
+			let is_empty = info.is_empty();
+			if is_empty {
+				diagnostics.tally("removed empty reading");
+			}
+			!is_empty
+		});
+		let has_readings = !self.reading_infos.is_empty();
+		if !has_readings {
+			diagnostics.tally("removed empty title");
+		}
+		has_readings
+	}
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ReadingInfo {
+	pub accents: Vec<u8>, // The Tokyo accents.
+	// The accents of other dialects, each with its dialect.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub dialect_accents: Vec<(Dialect, u8)>,
+	pub accent_conflict: bool,
+	pub predicted_accent: Option<u8>, // An accent inferred from the components of the decomposition, if unknown.
+	// Each accent with the 1-based index of the etymology that gives it, if the page has several.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub etymology_accents: Vec<(u8, u8)>,
+	pub decomposition: Option<DecompositionInfo>,
+	#[serde(default, skip_serializing_if = "PronInfo::is_empty")]
+	pub pron: PronInfo,
+}
+
+impl ReadingInfo {
+	// Whether nothing was inferred for the reading.
+	pub fn is_empty(&self) -> bool {
+		self.accents.is_empty()
+			&& self.dialect_accents.is_empty()
+			&& self.decomposition.is_none()
+			&& self.pron.is_empty()
+	}
+}
+
+// Details of the pronunciation of a reading other than its accent.
+#[derive(Default, Serialize, Deserialize)]
+pub struct PronInfo {
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub audio: Vec<String>, // Audio filenames from Wikimedia Commons.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub qualifiers: Vec<String>, // Qualifiers of the reading (e.g. dated).
+}
+
+impl PronInfo {
+	pub fn is_empty(&self) -> bool {
+		self.audio.is_empty() && self.qualifiers.is_empty()
+	}
+}
+
+// A title and its readings, as written by the JSON formats.
+#[derive(Serialize)]
+pub struct Record<'a> {
+	pub title: &'a str,
+	pub kana_only: bool,
+	pub readings: Vec<ReadingRecord<'a>>,
+	#[serde(skip_serializing_if = "<[_]>::is_empty")]
+	pub warnings: &'a [String],
+}
+
+#[derive(Serialize)]
+pub struct ReadingRecord<'a> {
+	pub reading: &'a str,
+	#[serde(flatten)]
+	pub info: &'a ReadingInfo,
+}
+
+impl<'a> Record<'a> {
+	pub fn new(title: &'a str, word_info: &'a WordInfo) -> Self {
+		let mut readings: Vec<_> =
+			word_info.reading_infos.iter().map(|(reading, info)| ReadingRecord { reading, info }).collect();
+		readings.sort_unstable_by_key(|x| x.reading);
+		Self { title, kana_only: word_info.kana_only, readings, warnings: &word_info.warnings }
+	}
+}
+
+// Infer the decomposition of a title by a kanjitab, tallying the reason for any failure.
+fn decompose(
+	title: &str, ja_kanjitab: JaKanjitab, readings: &HashSet<String>, diagnostics: &Diagnostics,
+) -> Option<DecompositionInfo> {
+	infer_decompositions(title, ja_kanjitab, readings, diagnostics)
+		.inspect_err(|e| diagnostics.tally(format!("decomposition failed: {e:?}")))
+		.ok()
+}
+
+// Extract what a page, or an etymology section of one, gives about its title.
+// The etymology is the 1-based index of the section, if the page has several.
+pub fn process(
+	title: &str, text: &str, etymology: Option<u8>, extraction: &mut Extraction, extractor: &Extractor,
+	diagnostics: &Diagnostics,
+) {
+	let Extraction { info, redirects, sentences } = extraction;
+	let mut sees: Vec<String> = Vec::new();
+	let mut ja_prons = Vec::new();
+	let mut ja_kanjitabs = Vec::new();
+	let mut ja_compounds = Vec::new();
+	let mut ja_poss = Vec::new();
+
+	for template in FindTemplates::new(text).recovering() {
+		let (name, arguments) = match template {
+			Ok(template) => template,
+			Err(e) => {
+				diagnostics.warn(title, format!("malformed wikitext at byte {}: {:?}", e.offset, e.kind));
+				continue;
+			},
+		};
+		if let Some(handler) = extractor.handlers.get(name.as_ref()) {
+			match handler(arguments) {
+				ExtractContribution::Pron(ja_pron) => ja_prons.push(ja_pron),
+				ExtractContribution::Kanjitab(ja_kanjitab) => ja_kanjitabs.push(ja_kanjitab),
+				ExtractContribution::Pos(ja_pos) => ja_poss.push(ja_pos),
+				ExtractContribution::Nothing => (),
+			}
+			continue;
+		}
+		match name.as_ref() {
+			"ja-romaji" | "ja-rom" => return,
+			"ja-see" | "ja-see-kango" | "ja-gv" => {
+				sees.extend(TemplateParameters::new(arguments).map(|x| x.to_string()).collect::<Vec<_>>())
+			},
+			"ja-pron" => match parse_ja_pron(arguments) {
+				Ok(ja_pron) => ja_prons.push(ja_pron),
+				Err(e) => diagnostics.warn(title, format!("skipped malformed ja-pron: {e:?}")),
+			},
+			"ja-kanjitab" => ja_kanjitabs.extend(parse_ja_kanjitab(arguments)),
+			"ja-compound" => ja_compounds.extend(parse_ja_compound(title, arguments)),
+			"ja-pos" => ja_poss.push(parse_ja_pos(&name, arguments)),
+			"ja-noun" | "ja-verb" | "ja-verb form" | "ja-verb-suru" | "ja-adj" | "ja-phrase" => {
+				ja_poss.push(parse_ja_pos(&name, arguments))
+			},
+			"ja-altread" => ja_poss.push(parse_ja_altread(arguments)),
+			"ja-usex" => {
+				let ja_usex = parse_ja_usex(arguments);
+				let translation =
+					ja_usex.translation.as_ref().map(|x| x.split_whitespace().collect::<Vec<_>>().join(" "));
+				let words = infer_usex_words(ja_usex);
+				let text = words.iter().map(|(word, _)| word.as_str()).collect();
+				sentences.push(Sentence { title: title.to_owned(), text, words, translation });
+			},
+			_ => continue,
+		}
+	}
+
+	// Compounds decompose the title only if no kanjitab does, as they give readings only by component.
+	if ja_kanjitabs.is_empty() {
+		ja_kanjitabs = ja_compounds;
+	}
+
+	if !sees.is_empty() && (ja_poss.is_empty() && ja_prons.is_empty()) {
+		if !ja_kanjitabs.is_empty() {
+			redirects.push(Redirect { title: title.to_owned(), ja_kanjitabs, sees });
+		}
+		return;
+	}
+
+	let word_info = info.entry(title.to_owned()).or_insert_with(|| WordInfo::new(title));
+
+	let mut readings = HashSet::new();
+	for ja_pron in ja_prons {
+		for info in infer_accent(title, ja_pron, diagnostics) {
+			if !(info.audio.is_empty() && info.qualifiers.is_empty()) {
+				let reading_info = word_info.reading_infos.entry(info.reading.clone()).or_default();
+				reading_info.pron.audio.extend(info.audio);
+				reading_info.pron.qualifiers.extend(info.qualifiers);
+			}
+			readings.insert(info.reading.clone());
+			// Accents of other dialects are kept apart, as the passes over accents assume Tokyo ones.
+			if info.dialect != Dialect::Tokyo {
+				if let Some(accent) = info.accent {
+					let reading_info = word_info.reading_infos.entry(info.reading.clone()).or_default();
+					reading_info.dialect_accents.push((info.dialect, accent));
+				}
+				continue;
+			}
+			if let Some(accent) = info.accent {
+				let reading_info = word_info.reading_infos.entry(info.reading.clone()).or_default();
+				let is_conflict = reading_info.accents.iter().any(|x| *x != accent);
+				match extractor.accent_conflicts {
+					AccentConflictPolicy::PreferFirst if !reading_info.accents.is_empty() => continue,
+					AccentConflictPolicy::Flag if is_conflict => {
+						diagnostics.warn(title, format!("conflicting accents for reading {}", info.reading));
+						reading_info.accent_conflict = true;
+					},
+					_ => (),
+				}
+				reading_info.accents.push(accent);
+				if let Some(etymology) = etymology {
+					reading_info.etymology_accents.push((etymology, accent));
+				}
+			}
+		}
+	}
+
+	for ja_pos in ja_poss {
+		readings.extend(infer_pos_readings(title, ja_pos, diagnostics));
+	}
+
+	word_info.readings.extend(readings);
+
+	for ja_kanjitab in ja_kanjitabs {
+		if let Some(decomposition) = decompose(title, ja_kanjitab, &word_info.readings, diagnostics) {
+			let reading = decomposition.reading();
+			let reading_info = word_info.reading_infos.entry(reading).or_default();
+			// NOTE: Assumes duplicate decompositions are identical.
+			if reading_info.decomposition.is_none() {
+				reading_info.decomposition = Some(decomposition);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::diagnostics::Level;
+
+	fn page(title: &str, body: &str) -> (String, String) {
+		(title.to_owned(), format!("==Japanese==\n{body}"))
+	}
+
+	#[test]
+	fn non_tokyo_accents_are_kept_with_their_dialect() {
+		let info = build_info(
+			[page("雨", "{{ja-pron|あめ|あめ|acc=1|acc2=2|acc2_loc=Kyoto}}\n{{ja-noun|あめ}}\n")].into_iter(),
+		);
+		let reading_info = &info["雨"].reading_infos["アメ"];
+		assert_eq!(reading_info.accents, [1]);
+		assert_eq!(reading_info.dialect_accents, [(Dialect::Keihan, 2)]);
+		let json = serde_json::to_value(Record::new("雨", &info["雨"])).unwrap();
+		assert_eq!(json["readings"][0]["dialect_accents"], serde_json::json!([["keihan", 2]]));
+	}
+
+	#[test]
+	fn usage_examples_are_split_into_words() {
+		let mut extraction = Extraction::default();
+		let text = "==Japanese==\n{{ja-usex|[[雨]] が 降る|あめ が ふる|It rains.}}\n";
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		process_page("雨", text, &mut extraction, &extractor, &Diagnostics::new());
+		let [sentence] = &extraction.sentences[..] else { panic!("expected one sentence") };
+		assert_eq!(sentence.text, "雨が降る");
+		assert_eq!(sentence.translation.as_deref(), Some("It rains."));
+		let words = [("雨", "アメ"), ("が", "ガ"), ("降る", "フル")]
+			.map(|(word, reading)| (word.to_owned(), Some(reading.to_owned())));
+		assert_eq!(sentence.words, words);
+	}
+
+	#[test]
+	fn redirects_resolve_the_same_after_a_round_trip() {
+		let pages = [
+			page("手帋", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手帖}}\n"),
+			page("手帖", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手紙}}\n"),
+			page("手紙", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-pron|てがみ|acc=0}}\n{{ja-noun|てがみ}}\n"),
+		];
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		let diagnostics = Diagnostics::new();
+		let mut extraction = Extraction::default();
+		for (title, text) in &pages {
+			process_page(title, text, &mut extraction, &extractor, &diagnostics);
+		}
+		let info_json = serde_json::to_string(&extraction.info).unwrap();
+		let redirects_json = serde_json::to_string(&extraction.redirects).unwrap();
+		let mut info: HashMap<String, WordInfo> = serde_json::from_str(&info_json).unwrap();
+		let redirects: Vec<Redirect> = serde_json::from_str(&redirects_json).unwrap();
+		resolve_redirects(redirects, &mut info, &diagnostics);
+
+		let records = |info: &HashMap<String, WordInfo>| {
+			let mut titles: Vec<_> = info.keys().collect();
+			titles.sort_unstable();
+			titles
+				.into_iter()
+				.map(|title| serde_json::to_value(Record::new(title, &info[title])).unwrap())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(records(&info), records(&build_info(pages.into_iter())));
+		assert_eq!(info["手帋"].reading_infos["テガミ"].decomposition.as_ref().unwrap().reading(), "テガミ");
+	}
+
+	#[test]
+	fn compounds_decompose_only_without_a_kanjitab() {
+		let pron = "{{ja-pron|てがみ|acc=0}}\n{{ja-noun|てがみ}}\n";
+		let compound = "{{ja-compound|手|て|紙|がみ}}\n";
+		let info = build_info(
+			[
+				page("手紙", &format!("{compound}{pron}")),
+				// The kanjitab lacks the rendaku of 紙, so it does not match, but the compound is still ignored.
+				page(
+					"手帋",
+					&format!("{{{{ja-kanjitab|て|かみ}}}}\n{{{{ja-compound|手|て|帋|がみ}}}}\n{pron}"),
+				),
+			]
+			.into_iter(),
+		);
+		let decomposition = |title: &str| info[title].reading_infos["テガミ"].decomposition.as_ref();
+		let html = decomposition("手紙").map(|x| x.to_ruby_html());
+		assert_eq!(html.as_deref(), Some("<ruby>手<rt>テ</rt></ruby><ruby>紙<rt>ガミ</rt></ruby>"));
+		assert!(decomposition("手帋").is_none());
+	}
+
+	#[test]
+	fn kanji_readings_are_split_by_yomi() {
+		let info = build_info(
+			[
+				page(
+					"日本",
+					"{{ja-kanjitab|に|ほん|yomi=o}}\n{{ja-pron|にほん|acc=2}}\n{{ja-noun|にほん}}\n",
+				),
+				page(
+					"日曜",
+					"{{ja-kanjitab|にち|よう|yomi=o}}\n{{ja-pron|にちよう|acc=3}}\n{{ja-noun|にちよう}}\n",
+				),
+				page(
+					"日向",
+					"{{ja-kanjitab|ひ|なた|yomi=k}}\n{{ja-pron|ひなた|acc=0}}\n{{ja-noun|ひなた}}\n",
+				),
+				page("日毎", "{{ja-kanjitab|ひ|ごと}}\n{{ja-pron|ひごと|acc=0}}\n{{ja-noun|ひごと}}\n"),
+			]
+			.into_iter(),
+		);
+		let readings = &aggregate_kanji_readings(&info)[&'日'];
+		let sorted = |readings: &HashMap<String, u32>| {
+			let mut readings: Vec<_> = readings.iter().map(|(reading, n)| (reading.clone(), *n)).collect();
+			readings.sort_unstable();
+			readings
+		};
+		assert_eq!(sorted(&readings.on), [("ニ".to_owned(), 1), ("ニチ".to_owned(), 1)]);
+		assert_eq!(sorted(&readings.kun), [("ヒ".to_owned(), 1)]);
+		assert_eq!(sorted(&readings.unclassified), [("ヒ".to_owned(), 1)]);
+	}
+
+	// Extract a single page under a given policy for accent conflicts, with its diagnostics.
+	fn extract(policy: AccentConflictPolicy, title: &str, body: &str) -> (WordInfo, Diagnostics) {
+		let diagnostics = Diagnostics::new();
+		let mut extraction = Extraction::default();
+		let (title, text) = page(title, body);
+		process_page(&title, &text, &mut extraction, &Extractor::new(policy), &diagnostics);
+		(extraction.info.remove(&title).unwrap(), diagnostics)
+	}
+
+	#[test]
+	fn conflicting_accents_follow_the_policy() {
+		let body = "{{ja-pron|はし|acc=1}}\n{{ja-pron|はし|acc=2}}\n{{ja-noun|はし}}\n";
+		let (word_info, _) = extract(AccentConflictPolicy::KeepAll, "橋", body);
+		assert_eq!(word_info.reading_infos["ハシ"].accents, [1, 2]);
+		let (word_info, _) = extract(AccentConflictPolicy::PreferFirst, "橋", body);
+		assert_eq!(word_info.reading_infos["ハシ"].accents, [1]);
+		let (word_info, diagnostics) = extract(AccentConflictPolicy::Flag, "橋", body);
+		assert_eq!(word_info.reading_infos["ハシ"].accents, [1, 2]);
+		assert!(word_info.reading_infos["ハシ"].accent_conflict);
+		assert_eq!(
+			diagnostics.messages_by_title(Level::Warning)["橋"],
+			["conflicting accents for reading ハシ"]
+		);
+	}
+
+	#[test]
+	fn registered_handlers_take_precedence() {
+		let mut extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		extractor.register("ja-pron-new", |arguments| match parse_ja_pron(arguments) {
+			Ok(ja_pron) => ExtractContribution::Pron(ja_pron),
+			Err(_) => ExtractContribution::Nothing,
+		});
+		extractor.register("ja-noun", |_| ExtractContribution::Nothing);
+		let diagnostics = Diagnostics::new();
+		let mut extraction = Extraction::default();
+		let (title, text) = page("雨", "{{ja-pron-new|あめ|acc=1}}\n{{ja-noun|あまり}}\n");
+		process_page(&title, &text, &mut extraction, &extractor, &diagnostics);
+		let word_info = &extraction.info["雨"];
+		assert_eq!(word_info.reading_infos["アメ"].accents, [1]);
+		assert!(!word_info.readings.contains("アマリ"));
+	}
+
+	#[test]
+	fn minimal_pairs_differ_in_accent_between_titles() {
+		let pages = [
+			page("箸", "{{ja-pron|はし|acc=1}}\n{{ja-noun|はし}}\n"),
+			page("橋", "{{ja-pron|はし|acc=2}}\n{{ja-noun|はし}}\n"),
+			page("端", "{{ja-pron|はし|acc=0}}\n{{ja-noun|はし}}\n"),
+			page("会う", "{{ja-pron|あう|acc=1}}\n{{ja-verb|あう}}\n"),
+			page("合う", "{{ja-pron|あう|acc=1}}\n{{ja-verb|あう}}\n"),
+		];
+		let pairs = find_minimal_pairs(&build_info(pages.into_iter()));
+		let expected = [("橋", 2), ("端", 0), ("箸", 1)].map(|(title, accent)| (title.to_owned(), accent));
+		assert_eq!(pairs, [("ハシ".to_owned(), expected.to_vec())]);
+	}
+
+	#[test]
+	fn pages_with_windows_line_endings_are_processed() {
+		let diagnostics = Diagnostics::new();
+		let mut extraction = Extraction::default();
+		let text = "==Japanese== \r\n===Etymology 1=== \r\n{{ja-pron|あめ|acc=1}}\r\n{{ja-noun|あめ}}\r\n";
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		process_page("雨", text, &mut extraction, &extractor, &diagnostics);
+		assert_eq!(extraction.info["雨"].reading_infos["アメ"].accents, [1]);
+	}
+
+	#[test]
+	fn pages_without_a_japanese_section_are_skipped() {
+		let diagnostics = Diagnostics::new();
+		let mut extraction = Extraction::default();
+		let text = "==Chinese==\n<!--\n==Japanese==\n-->\n{{ja-pron|あめ|acc=1}}\n";
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		process_page("雨", text, &mut extraction, &extractor, &diagnostics);
+		assert!(extraction.info.is_empty());
+		assert_eq!(diagnostics.messages_by_title(Level::Note)["雨"], ["page has no Japanese section"]);
+	}
+
+	#[test]
+	fn titles_without_kanji_are_kana_only() {
+		let (word_info, _) =
+			extract(AccentConflictPolicy::KeepAll, "ひらがな", "{{ja-pron|ひらがな|acc=3}}\n");
+		assert!(word_info.kana_only);
+		let (word_info, _) = extract(AccentConflictPolicy::KeepAll, "雨", "{{ja-pron|あめ|acc=1}}\n");
+		assert!(!word_info.kana_only);
+	}
+
+	#[test]
+	fn duplicate_accents_are_collapsed_once_finalized() {
+		let body = "{{ja-pron|はし|acc=2}}\n{{ja-pron|はし|acc=0}}\n{{ja-pron|はし|acc=2}}\n";
+		let (mut word_info, _) = extract(AccentConflictPolicy::KeepAll, "橋", body);
+		assert_eq!(word_info.reading_infos["ハシ"].accents, [2, 0, 2]);
+		word_info.finalize_accents();
+		assert_eq!(word_info.reading_infos["ハシ"].accents, [0, 2]);
+	}
+
+	#[test]
+	fn audio_files_are_kept_with_their_reading() {
+		let (word_info, _) =
+			extract(AccentConflictPolicy::KeepAll, "東京", "{{ja-pron|とうきょう|a=Ja-Tokyo.ogg}}\n");
+		assert_eq!(word_info.reading_infos["トウキョウ"].pron.audio, ["Ja-Tokyo.ogg"]);
+	}
+
+	#[test]
+	fn malformed_pronunciations_are_skipped_with_a_warning() {
+		let body = "{{ja-pron|あめ|acc=foo}}\n{{ja-pron|あめ|acc=1}}\n";
+		let (word_info, diagnostics) = extract(AccentConflictPolicy::KeepAll, "雨", body);
+		assert_eq!(word_info.reading_infos["アメ"].accents, [1]);
+		let warnings = diagnostics.messages_by_title(Level::Warning);
+		assert_eq!(warnings["雨"], ["skipped malformed ja-pron: InvalidAccent(\"foo\")"]);
+	}
+
+	#[test]
+	fn qualifiers_are_kept_with_their_reading() {
+		let (word_info, _) =
+			extract(AccentConflictPolicy::KeepAll, "東京", "{{ja-pron|とうけい<q:dated>|acc=0}}\n");
+		assert_eq!(word_info.reading_infos["トウケイ"].pron.qualifiers, ["dated"]);
+	}
+
+	#[test]
+	fn failed_decompositions_are_tallied_by_error() {
+		let body = "{{ja-kanjitab|て|がみ}}\n{{ja-pron|あめ|acc=1}}\n";
+		let (_, diagnostics) = extract(AccentConflictPolicy::KeepAll, "手紙", body);
+		assert_eq!(diagnostics.tallies(), [("decomposition failed: Mismatch".to_owned(), 1)].into());
+		let (_, diagnostics) = extract(AccentConflictPolicy::KeepAll, "手紙", "{{ja-kanjitab|て}}\n");
+		assert_eq!(diagnostics.tallies(), [("decomposition failed: Incomplete".to_owned(), 1)].into());
+	}
+
+	#[test]
+	fn redirect_chains_resolve_and_cycles_end() {
+		let pages = [
+			page("手紙", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-pron|てがみ|acc=0}}\n{{ja-noun|てがみ}}\n"),
+			page("手帖", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手帋}}\n"),
+			page("手帋", "{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手紙}}\n"),
+			page("甲", "{{ja-kanjitab|こう}}\n{{ja-see|乙}}\n"),
+			page("乙", "{{ja-kanjitab|おつ}}\n{{ja-see|甲}}\n"),
+		];
+		let info = build_info(pages.into_iter());
+		let decomposition = info["手帖"].reading_infos["テガミ"].decomposition.as_ref().unwrap();
+		assert_eq!(decomposition.reading(), "テガミ");
+		assert!(!info.contains_key("甲") && !info.contains_key("乙"));
+	}
+
+	#[test]
+	fn accents_are_attributed_to_their_etymology() {
+		let body = "===Etymology 1===\n{{ja-pron|はし|acc=2}}\n===Etymology 2===\n{{ja-pron|はし|acc=0}}\n";
+		let (word_info, _) = extract(AccentConflictPolicy::KeepAll, "はし", body);
+		let reading_info = &word_info.reading_infos["ハシ"];
+		assert_eq!(reading_info.accents, [2, 0]);
+		assert_eq!(reading_info.etymology_accents, [(1, 2), (2, 0)]);
+		// A single etymology is not numbered.
+		let (word_info, _) =
+			extract(AccentConflictPolicy::KeepAll, "箸", "===Etymology 1===\n{{ja-pron|はし|acc=1}}\n");
+		assert!(word_info.reading_infos["ハシ"].etymology_accents.is_empty());
+	}
+
+	#[test]
+	fn reading_index_lists_every_title() {
+		let pages = [
+			page("雨", "{{ja-pron|あめ|acc=1}}\n"),
+			page("飴", "{{ja-pron|あめ|acc=0}}\n"),
+			page("箸", "{{ja-pron|はし|acc=1}}\n"),
+		];
+		let index = index_readings(&build_info(pages.into_iter()));
+		let expected = [("アメ", vec!["雨", "飴"]), ("ハシ", vec!["箸"])];
+		assert_eq!(
+			index,
+			expected.map(|(reading, titles)| (
+				reading.to_owned(),
+				titles.into_iter().map(str::to_owned).collect()
+			))
+		);
+	}
+
+	#[test]
+	fn stray_braces_give_one_warning() {
+		let body = format!("{{{{ja-pron|あめ|acc=1}}}}{}{{{{{{x\n", " ".repeat(1000));
+		let (word_info, diagnostics) = extract(AccentConflictPolicy::KeepAll, "雨", &body);
+		assert_eq!(word_info.reading_infos["アメ"].accents, [1]);
+		assert_eq!(diagnostics.messages_by_title(Level::Warning)["雨"].len(), 1);
+	}
+
+	#[test]
+	fn compounds_of_other_kanji_are_ignored() {
+		let info = build_info(
+			[
+				page("国連", "{{ja-compound|国際|こくさい|連合|れんごう}}\n{{ja-pron|こくれん|acc=0}}\n"),
+				page("手", "{{ja-compound|手紙|てがみ}}\n{{ja-pron|て|acc=1}}\n"),
+			]
+			.into_iter(),
+		);
+		assert!(info["国連"].reading_infos["コクレン"].decomposition.is_none());
+		assert!(info["手"].reading_infos["テ"].decomposition.is_none());
+	}
+
+	#[test]
+	fn empty_readings_and_then_titles_are_removed() {
+		let pages = [page("雨", "{{ja-pron|あめ|acc=1}}\n{{ja-noun|あめ}}\n")];
+		let mut info = build_info(pages.into_iter());
+		info.get_mut("雨").unwrap().reading_infos.insert("アマ".to_owned(), ReadingInfo::default());
+		let mut word_info = WordInfo::new("飴");
+		word_info.reading_infos.insert("アメ".to_owned(), ReadingInfo::default());
+		info.insert("飴".to_owned(), word_info);
+		let diagnostics = Diagnostics::new();
+		remove_empty_readings(&mut info, &diagnostics);
+		assert_eq!(info.keys().collect::<Vec<_>>(), ["雨"]);
+		assert_eq!(info["雨"].reading_infos.keys().collect::<Vec<_>>(), ["アメ"]);
+		let tallies = diagnostics.tallies();
+		assert_eq!((tallies["removed empty reading"], tallies["removed empty title"]), (2, 1));
+	}
+
+	#[test]
+	fn usage_examples_may_annotate_their_words() {
+		let mut extraction = Extraction::default();
+		let text = "==Japanese==\n{{ja-usex|[[雨]]^あめ が [[降る]]^ふる 傘|t=It rains.}}\n";
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		process_page("雨", text, &mut extraction, &extractor, &Diagnostics::new());
+		let [sentence] = &extraction.sentences[..] else { panic!("expected one sentence") };
+		assert_eq!(sentence.text, "雨が降る傘");
+		let words = [("雨", Some("アメ")), ("が", Some("ガ")), ("降る", Some("フル")), ("傘", None)]
+			.map(|(word, reading)| (word.to_owned(), reading.map(str::to_owned)));
+		assert_eq!(sentence.words, words);
+	}
+}
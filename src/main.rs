@@ -1,206 +1,447 @@
-mod infer;
-mod ja;
-mod parse;
-mod wikitext;
-
 use std::{
-	collections::{HashMap, HashSet},
+	borrow::Cow,
+	collections::HashMap,
 	fs::File,
-	io::{BufReader, Read as _},
+	io::{self, BufRead, BufReader, BufWriter, Read, Write as _},
+	mem,
+	path::{Path, PathBuf},
 };
 
-use crate::{
-	infer::{Atom, infer_accent, infer_decompositions, infer_pos_readings},
-	parse::{JaKanjitab, parse_ja_altread, parse_ja_kanjitab, parse_ja_pos, parse_ja_pron},
-	wikitext::{FindTemplates, TemplateParameters},
+use serde::{Serialize, de::DeserializeOwned};
+
+use wiktionary_hatsuon::{
+	AccentConflictPolicy, Extraction, Extractor, KanjiReadings, Record, Sentence, aggregate_kanji_readings,
+	diagnostics::{Diagnostics, Level},
+	finalize_accents, find_minimal_pairs, index_readings,
+	infer::Dialect,
+	ja::is_ideograph,
+	predict_accents, process_page, remove_empty_readings, resolve_redirects,
+	stream::{StreamFormat, StreamingWriter},
 };
 
 fn main() {
-	let input = File::open("scripts/enwiktionary-20250920/enwiktionary.bin").unwrap();
-	let mut input = BufReader::new(input);
-	let mut length_prefix = [0u8; 8];
-	let mut info = HashMap::new();
-	let mut redirects: Vec<Redirect> = Vec::new();
-	loop {
-		if input.read_exact(&mut length_prefix).is_err() {
-			break;
+	let options = parse_options();
+	let input_path = &options.input;
+	let diagnostics = Diagnostics::new();
+	let extraction = if let Command::ResolveRedirects { redirects } = &options.command {
+		Extraction { info: read_json(input_path), redirects: read_json(redirects), sentences: Vec::new() }
+	} else {
+		let extractor = Extractor::new(options.accent_conflicts);
+		let mut extraction = Extraction::default();
+		let format = match options.format {
+			Format::JsonArray => StreamFormat::JsonArray,
+			_ => StreamFormat::JsonLines,
+		};
+		let mut writer =
+			options.stream.then(|| StreamingWriter::new(BufWriter::new(io::stdout().lock()), format));
+		let process = |title: &str, text: &str| match &mut writer {
+			Some(writer) => writer.process_page(title, text, &extractor, &diagnostics).unwrap(),
+			None => process_page(title, text, &mut extraction, &extractor, &diagnostics),
+		};
+		match options.input_format {
+			InputFormat::Records => read_records(input_path, process),
+			#[cfg(feature = "xml")]
+			InputFormat::Xml => read_xml(input_path, process),
 		}
-		let mut title = vec![0; u64::from_le_bytes(length_prefix) as _];
-		input.read_exact(&mut title).unwrap();
-		let title = String::from_utf8(title).unwrap();
-		input.read_exact(&mut length_prefix).unwrap();
-		let mut text = vec![0; u64::from_le_bytes(length_prefix) as _];
-		input.read_exact(&mut text).unwrap();
-		let text = String::from_utf8(text).unwrap();
-
-		// Narrow text to Japanese section.
-		// Assumes no fake new sections in a multi-line comment.
-		const PREFIX: &str = "==Japanese==\n";
-		let start = text.find(PREFIX).unwrap() + PREFIX.len();
-		let mut text = &text[start..];
-		for line in text.lines() {
-			if line.len() > 2 && line.get(0..2) == Some("==") && line.as_bytes().get(2) != Some(&b'=') {
-				text = &text[..unsafe { line.as_ptr().offset_from_unsigned(text.as_ptr()) }];
-				break;
+
+		if let Some(mut writer) = writer {
+			let sentences = mem::take(&mut writer.sentences);
+			writer.finish(&diagnostics).unwrap();
+			diagnostics.emit();
+			if let Some(path) = &options.sentences {
+				write_sentences(path, sentences).unwrap_or_else(|e| fail(path, e));
 			}
+			return;
 		}
+		extraction
+	};
 
-		// Split text by etymology if multiple such sections exist, then process each subtext.
-		// NOTE: Sometimes, a text will have "Etymology 1" but only have one etymology. (e.g. 操)
-		// NOTE: Sometimes, a text will have multiple "Etymology" sections. (e.g. 薄)
-		// Assumes "===Etymology" does not appear in a comment somewhere.
-		const ETYMOLOGY_PREFIX: &str = "===Etymology";
-		if text.match_indices(ETYMOLOGY_PREFIX).map(|_| 1).sum::<u32>() > 1 {
-			while let Some(i) = text.find(ETYMOLOGY_PREFIX) {
-				text = &text[i + ETYMOLOGY_PREFIX.len()..];
-				let i = text.find("===\n").unwrap() + "===\n".len();
-				text = &text[i..];
-				let mut current_text = text;
-				for line in text.lines() {
-					if line.len() > 3
-						&& line.get(0..3) == Some("===")
-						&& line.as_bytes().get(3) != Some(&b'=')
-					{
-						current_text = &current_text
-							[..unsafe { line.as_ptr().offset_from_unsigned(current_text.as_ptr()) }];
-						break;
-					}
-				}
-				process(&title, current_text, &mut redirects, &mut info);
-				text = &text[current_text.len()..];
-			}
-		} else {
-			process(&title, text, &mut redirects, &mut info);
+	let Extraction { mut info, redirects, sentences } = extraction;
+	if let Command::DeferRedirects { info: info_path, redirects: redirects_path } = &options.command {
+		diagnostics.emit();
+		if let Some(path) = &options.sentences {
+			write_sentences(path, sentences).unwrap_or_else(|e| fail(path, e));
 		}
+		write_json(info_path, &info).unwrap_or_else(|e| fail(info_path, e));
+		write_json(redirects_path, &redirects).unwrap_or_else(|e| fail(redirects_path, e));
+		return;
 	}
+	resolve_redirects(redirects, &mut info, &diagnostics);
 
-	'redirects: for redirect in redirects {
-		for see in redirect.sees {
-			let readings = {
-				let Some(word_info) = info.get(&see) else {
-					continue;
-				};
-				word_info.readings.clone()
-			};
+	if options.predict_accents {
+		predict_accents(&mut info);
+	}
+	finalize_accents(&mut info);
+	remove_empty_readings(&mut info, &diagnostics);
 
-			for ja_kanjitab in redirect.ja_kanjitabs {
-				let Ok(decomposition) = infer_decompositions(&redirect.title, ja_kanjitab, &readings) else {
-					continue;
-				};
-				let new_info = info
-					.entry(redirect.title.clone())
-					.or_insert_with(|| WordInfo { reading_infos: HashMap::new(), readings: HashSet::new() });
-
-				let reading = decomposition.reading();
-				let reading_info = new_info
-					.reading_infos
-					.entry(reading)
-					.or_insert(ReadingInfo { accents: Vec::new(), decomposition: None });
-				// NOTE: Assumes duplicate decompositions (e.g. 綽約) are identical.
-				if reading_info.decomposition.is_none() {
-					reading_info.decomposition = Some(decomposition.atoms);
-				}
+	if options.inline_warnings {
+		for (title, warnings) in diagnostics.messages_by_title(Level::Warning) {
+			if let Some(word_info) = info.get_mut(&title) {
+				word_info.warnings = warnings;
 			}
-
-			continue 'redirects;
 		}
 	}
 
-	assert!(info.iter().all(|(_, info)| {
-		info.reading_infos.iter().all(|(_, info)| !info.accents.is_empty() || info.decomposition.is_some())
-	}));
+	diagnostics.emit();
+
+	if let Some(path) = &options.sentences {
+		write_sentences(path, sentences).unwrap_or_else(|e| fail(path, e));
+	}
+
+	// Keep only titles containing any of the given kanji, if any are given.
+	let contains_kanji = &options.contains_kanji;
+	if !contains_kanji.is_empty() {
+		info.retain(|title, _| title.chars().any(|c| contains_kanji.contains(&c)));
+	}
 
-	println!("{}", info.len());
-	// for (title, info) in info {
-	// 	for (reading, info) in info.reading_infos {
-	// 		println!("{title}.{reading}: {:?} + {:?}", info.accents, info.decomposition);
-	// 	}
-	// }
+	match options.format {
+		Format::Summary => println!("{}", info.len()),
+		Format::KanjiReadings => {
+			let mut kanji_readings: Vec<_> = aggregate_kanji_readings(&info).into_iter().collect();
+			kanji_readings.sort_unstable_by_key(|(kanji, _)| *kanji);
+			// The on'yomi, kun'yomi, and unclassified readings are given in turn, each most frequent first.
+			let format_readings = |readings: HashMap<String, u32>| {
+				let mut readings: Vec<_> = readings.into_iter().collect();
+				readings.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+				readings.iter().map(|(reading, n)| format!("{reading}:{n}")).collect::<Vec<_>>().join(" ")
+			};
+			for (kanji, KanjiReadings { on, kun, unclassified }) in kanji_readings {
+				let (on, kun, unclassified) =
+					(format_readings(on), format_readings(kun), format_readings(unclassified));
+				println!("{kanji}\t{on}\t{kun}\t{unclassified}");
+			}
+		},
+		Format::MinimalPairs => {
+			for (reading, titles) in find_minimal_pairs(&info) {
+				let titles = titles
+					.iter()
+					.map(|(title, accent)| format!("{title}:{accent}"))
+					.collect::<Vec<_>>()
+					.join(" ");
+				println!("{reading}\t{titles}");
+			}
+		},
+		Format::ReadingIndex => {
+			for (reading, titles) in index_readings(&info) {
+				println!("{reading}\t{}", titles.join(" "));
+			}
+		},
+		Format::JsonArray => {
+			let mut titles: Vec<_> = info.iter().collect();
+			titles.sort_unstable_by_key(|(title, _)| *title);
+			let mut output = BufWriter::new(std::io::stdout().lock());
+			write!(output, "[").unwrap();
+			for (i, (title, word_info)) in titles.into_iter().enumerate() {
+				writeln!(output, "{}", if i == 0 { "" } else { "," }).unwrap();
+				serde_json::to_writer(&mut output, &Record::new(title, word_info)).unwrap();
+			}
+			writeln!(output, "\n]").unwrap();
+			output.flush().unwrap();
+		},
+		Format::JsonLines => {
+			let mut titles: Vec<_> = info.iter().collect();
+			titles.sort_unstable_by_key(|(title, _)| *title);
+			let mut output = BufWriter::new(std::io::stdout().lock());
+			for (title, word_info) in titles {
+				serde_json::to_writer(&mut output, &Record::new(title, word_info)).unwrap();
+				writeln!(output).unwrap();
+			}
+			output.flush().unwrap();
+		},
+		Format::Csv => {
+			let mut titles: Vec<_> = info.iter().collect();
+			titles.sort_unstable_by_key(|(title, _)| *title);
+			let mut output = BufWriter::new(std::io::stdout().lock());
+			writeln!(output, "title,reading,accent,dialect").unwrap();
+			for (title, word_info) in titles {
+				for reading in Record::new(title, word_info).readings {
+					let row = format!("{},{}", escape_csv(title), escape_csv(reading.reading));
+					if reading.info.accents.is_empty() && reading.info.dialect_accents.is_empty() {
+						writeln!(output, "{row},,").unwrap();
+					}
+					for accent in &reading.info.accents {
+						writeln!(output, "{row},{accent},{}", Dialect::Tokyo.name()).unwrap();
+					}
+					for (dialect, accent) in &reading.info.dialect_accents {
+						writeln!(output, "{row},{accent},{}", escape_csv(dialect.name())).unwrap();
+					}
+				}
+			}
+			output.flush().unwrap();
+		},
+		#[cfg(feature = "sqlite")]
+		Format::Sqlite(path) => {
+			wiktionary_hatsuon::sqlite::write_database(&path, &info).unwrap_or_else(|e| fail(&path, e));
+		},
+	}
+}
+
+// Quote a CSV field if it contains a comma, quote, or line break, doubling any quotes (RFC 4180).
+fn escape_csv(field: &str) -> Cow<'_, str> {
+	if field.contains([',', '"', '\r', '\n']) {
+		Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+	} else {
+		Cow::Borrowed(field)
+	}
 }
 
-struct Redirect {
-	title: String,
-	// NOTE: There may be multiple redirects and multiple kanji tables (see 米[メートル|メーター]).
-	ja_kanjitabs: Vec<JaKanjitab>,
-	sees: Vec<String>,
+// Write the example sentences to a file, one per line, with any translation last.
+fn write_sentences(path: &Path, sentences: Vec<Sentence>) -> io::Result<()> {
+	let mut sentence_output = BufWriter::new(File::create(path)?);
+	for sentence in sentences {
+		let words = sentence
+			.words
+			.iter()
+			.map(|(word, reading)| match reading {
+				Some(reading) => format!("{word}:{reading}"),
+				None => word.clone(),
+			})
+			.collect::<Vec<_>>()
+			.join(" ");
+		let translation = sentence.translation.as_deref().unwrap_or_default();
+		writeln!(sentence_output, "{}\t{}\t{words}\t{translation}", sentence.title, sentence.text)?;
+	}
+	sentence_output.flush()
 }
 
-struct WordInfo {
-	reading_infos: HashMap<String, ReadingInfo>,
-	readings: HashSet<String>,
+// Write a value as JSON to a file, such as the parts of a deferred extraction.
+fn write_json(path: &Path, value: &impl Serialize) -> io::Result<()> {
+	let mut output = BufWriter::new(File::create(path)?);
+	serde_json::to_writer(&mut output, value)?;
+	output.flush()
 }
 
-struct ReadingInfo {
-	accents: Vec<u8>,
-	decomposition: Option<Vec<Atom>>,
+// Read a value written by write_json, exiting if it cannot be read.
+fn read_json<T: DeserializeOwned>(path: &Path) -> T {
+	File::open(path)
+		.map_err(serde_json::Error::io)
+		.and_then(|input| serde_json::from_reader(BufReader::new(input)))
+		.unwrap_or_else(|e| {
+			eprintln!("failed to read {}: {e}", path.display());
+			std::process::exit(1);
+		})
 }
 
-fn process(title: &str, text: &str, redirects: &mut Vec<Redirect>, info: &mut HashMap<String, WordInfo>) {
-	let mut sees: Vec<String> = Vec::new();
-	let mut ja_prons = Vec::new();
-	let mut ja_kanjitabs = Vec::new();
-	let mut ja_poss = Vec::new();
+// Report an error with an output file, and exit.
+fn fail(path: &Path, e: impl std::fmt::Display) -> ! {
+	eprintln!("failed to write {}: {e}", path.display());
+	std::process::exit(1);
+}
 
-	for (name, arguments) in FindTemplates::new(text) {
-		match name {
-			"ja-romaji" | "ja-rom" => return,
-			"ja-see" | "ja-see-kango" | "ja-gv" => {
-				sees.extend(TemplateParameters::new(arguments).map(|x| x.to_string()).collect::<Vec<_>>())
+// Read each record of a filtered dump, skipping any that are not UTF-8 and stopping at any that is cut short.
+fn read_records(input_path: &Path, mut process: impl FnMut(&str, &str)) {
+	let mut input = BufReader::new(File::open(input_path).unwrap());
+	let mut offset = 0;
+	loop {
+		let (title, text) = match read_record(&mut input) {
+			Ok(Some(record)) => record,
+			Ok(None) => break,
+			Err(e) => {
+				eprintln!("stopped reading at byte {offset}: {e}");
+				break;
 			},
-			"ja-pron" => ja_prons.push(parse_ja_pron(arguments).unwrap()),
-			"ja-kanjitab" => ja_kanjitabs.extend(parse_ja_kanjitab(arguments)),
-			"ja-pos" => ja_poss.push(parse_ja_pos(true, arguments)),
-			"ja-noun" | "ja-verb" | "ja-verb form" | "ja-verb-suru" | "ja-adj" | "ja-phrase" => {
-				ja_poss.push(parse_ja_pos(false, arguments))
+		};
+		let start = offset;
+		offset += 16 + title.len() + text.len();
+		match (String::from_utf8(title), String::from_utf8(text)) {
+			(Ok(title), Ok(text)) => process(&title, &text),
+			(title, _) => {
+				let title = title.unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+				eprintln!("skipped record {title} at byte {start}: invalid UTF-8");
 			},
-			"ja-altread" => ja_poss.push(parse_ja_altread(arguments)),
-			_ => continue,
 		}
 	}
+}
 
-	if !sees.is_empty() && (ja_poss.is_empty() && ja_prons.is_empty()) {
-		if !ja_kanjitabs.is_empty() {
-			redirects.push(Redirect { title: title.to_owned(), ja_kanjitabs, sees });
+// Read each Japanese page of an XML dump, stopping at any malformed XML.
+#[cfg(feature = "xml")]
+fn read_xml(input_path: &Path, mut process: impl FnMut(&str, &str)) {
+	for page in wiktionary_hatsuon::dump::open_xml(input_path).unwrap() {
+		match page {
+			Ok((title, text)) => process(&title, &text),
+			Err(e) => {
+				eprintln!("stopped reading at {e}");
+				break;
+			},
 		}
-		return;
 	}
+}
 
-	let word_info = info
-		.entry(title.to_owned())
-		.or_insert(WordInfo { reading_infos: HashMap::new(), readings: HashSet::new() });
-
-	let mut readings = HashSet::new();
-	for ja_pron in ja_prons {
-		for info in infer_accent(title, ja_pron) {
-			readings.insert(info.reading.clone());
-			if let Some(accent) = info.accent {
-				word_info
-					.reading_infos
-					.entry(info.reading)
-					.or_insert(ReadingInfo { accents: Vec::new(), decomposition: None })
-					.accents
-					.push(accent);
-			}
-		}
+// Read a record of the dump: a title and a text, each prefixed by its length as a little-endian u64.
+// Returns None at the end of the input, and an error if the record is cut short.
+fn read_record(input: &mut impl BufRead) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+	if input.fill_buf()?.is_empty() {
+		return Ok(None);
 	}
+	let title = read_field(input)?;
+	let text = read_field(input)?;
+	Ok(Some((title, text)))
+}
 
-	for ja_pos in ja_poss {
-		readings.extend(infer_pos_readings(ja_pos));
+fn read_field(input: &mut impl Read) -> io::Result<Vec<u8>> {
+	let mut length_prefix = [0u8; 8];
+	input.read_exact(&mut length_prefix)?;
+	let length = u64::from_le_bytes(length_prefix);
+	// NOTE: The length is not trusted for allocation, as a corrupt prefix may be arbitrarily large.
+	let mut field = Vec::new();
+	input.take(length).read_to_end(&mut field)?;
+	if (field.len() as u64) < length {
+		return Err(io::ErrorKind::UnexpectedEof.into());
 	}
+	Ok(field)
+}
 
-	word_info.readings.extend(readings);
+struct Options {
+	command: Command,
+	input: PathBuf, // The dump to read, or the info of a deferred extraction for resolve-redirects.
+	input_format: InputFormat,
+	accent_conflicts: AccentConflictPolicy,
+	format: Format,
+	predict_accents: bool,
+	contains_kanji: Vec<char>,
+	inline_warnings: bool,
+	stream: bool,               // Whether to write JSON as each title is final; see StreamingWriter.
+	sentences: Option<PathBuf>, // Where to write the example sentences, if anywhere.
+}
 
-	for ja_kanjitab in ja_kanjitabs {
-		if let Ok(decomposition) = infer_decompositions(title, ja_kanjitab, &word_info.readings) {
-			let reading = decomposition.reading();
-			let reading_info = word_info
-				.reading_infos
-				.entry(reading)
-				.or_insert(ReadingInfo { accents: Vec::new(), decomposition: None });
-			// NOTE: Assumes duplicate decompositions are identical.
-			if reading_info.decomposition.is_none() {
-				reading_info.decomposition = Some(decomposition.atoms);
-			}
+enum Command {
+	Extract,
+	// Write the extraction to JSON files instead, leaving its redirects unresolved.
+	DeferRedirects { info: PathBuf, redirects: PathBuf },
+	// Resolve the redirects of a deferred extraction, and then write it as Extract would.
+	ResolveRedirects { redirects: PathBuf },
+}
+
+#[derive(Clone, Copy)]
+enum InputFormat {
+	Records, // Length-prefixed titles and texts, as written by scripts/filter_wiktionary.rs.
+	// An XML dump, which may be bzip2-compressed.
+	#[cfg(feature = "xml")]
+	Xml,
+}
+
+enum Format {
+	Summary,       // The number of titles.
+	KanjiReadings, // The on'yomi, kun'yomi, and other readings of each kanji, with their frequencies.
+	MinimalPairs,  // The readings shared by titles with different accents.
+	ReadingIndex,  // The titles of each reading.
+	JsonArray,     // A JSON array of records, one per title.
+	JsonLines,     // A JSON record per line, one per title.
+	Csv,           // A CSV row per accent of each reading, or per reading without any.
+	// A SQLite database, written to the given path.
+	#[cfg(feature = "sqlite")]
+	Sqlite(PathBuf),
+}
+
+fn parse_options() -> Options {
+	let mut inputs = Vec::new();
+	let mut options = Options {
+		command: Command::Extract,
+		input: PathBuf::new(),
+		input_format: InputFormat::Records,
+		accent_conflicts: AccentConflictPolicy::KeepAll,
+		format: Format::JsonLines,
+		predict_accents: false,
+		contains_kanji: Vec::new(),
+		inline_warnings: false,
+		stream: false,
+		sentences: None,
+	};
+	let mut args = std::env::args().skip(1).peekable();
+	let is_resolving = args.next_if_eq("resolve-redirects").is_some();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--accent-conflicts" => {
+				options.accent_conflicts = match args.next().as_deref() {
+					Some("keep") => AccentConflictPolicy::KeepAll,
+					Some("first") => AccentConflictPolicy::PreferFirst,
+					Some("flag") => AccentConflictPolicy::Flag,
+					_ => usage(),
+				}
+			},
+			"--format" => {
+				options.format = match args.next().as_deref() {
+					Some("summary") => Format::Summary,
+					Some("kanji-readings") => Format::KanjiReadings,
+					Some("minimal-pairs") => Format::MinimalPairs,
+					Some("reading-index") => Format::ReadingIndex,
+					Some("json-array") => Format::JsonArray,
+					Some("json-lines") => Format::JsonLines,
+					Some("csv") => Format::Csv,
+					#[cfg(feature = "sqlite")]
+					Some("sqlite") => Format::Sqlite(args.next().map(PathBuf::from).unwrap_or_else(|| usage())),
+					_ => usage(),
+				}
+			},
+			"--predict-accents" => options.predict_accents = true,
+			"--inline-warnings" => options.inline_warnings = true,
+			"--stream" => options.stream = true,
+			"--defer-redirects" if !is_resolving => {
+				let mut path = || args.next().map(PathBuf::from).unwrap_or_else(|| usage());
+				options.command = Command::DeferRedirects { info: path(), redirects: path() };
+			},
+			"--sentences" => {
+				options.sentences = Some(args.next().map(PathBuf::from).unwrap_or_else(|| usage()))
+			},
+			#[cfg(feature = "xml")]
+			"--xml" if !is_resolving => {
+				options.input_format = InputFormat::Xml;
+				inputs.push(args.next().map(PathBuf::from).unwrap_or_else(|| usage()));
+			},
+			"--contains-kanji" => {
+				let kanji = args.next().unwrap_or_else(|| usage());
+				let mut kanji = kanji.chars();
+				match (kanji.next(), kanji.next()) {
+					(Some(kanji), None) if is_ideograph(kanji) => options.contains_kanji.push(kanji),
+					_ => usage(),
+				}
+			},
+			_ if arg.starts_with("--") => usage(),
+			_ => inputs.push(PathBuf::from(arg)),
 		}
 	}
+	let mut inputs = inputs.into_iter();
+	let input = inputs.next();
+	if is_resolving {
+		options.command = Command::ResolveRedirects { redirects: inputs.next().unwrap_or_else(|| usage()) };
+	}
+	// A deferred extraction is read as JSON, so it cannot be streamed.
+	if inputs.next().is_some()
+		|| options.stream && !matches!(options.command, Command::Extract)
+		|| is_resolving && options.sentences.is_some()
+	{
+		usage();
+	}
+	// Streaming writes JSON Lines or a JSON array, and cannot wait for passes over every title.
+	if options.stream
+		&& (!matches!(options.format, Format::JsonLines | Format::JsonArray)
+			|| options.predict_accents
+			|| options.inline_warnings
+			|| !options.contains_kanji.is_empty())
+	{
+		usage();
+	}
+	options.input =
+		input.or_else(|| std::env::var_os("HATSUON_INPUT").map(PathBuf::from)).unwrap_or_else(|| usage());
+	options
+}
+
+const USAGE: &str = "usage: wiktionary-hatsuon [options] <input.bin>
+	(the input may instead be given by HATSUON_INPUT)
+       wiktionary-hatsuon resolve-redirects [options] <info.json> <redirects.json>
+	(resolve the redirects of an extraction written by --defer-redirects)
+	--xml <dump.xml> (with the xml feature: read an XML dump, such as *-pages-articles.xml.bz2, as the input)
+	--accent-conflicts keep|first|flag
+	--format json-lines|json-array|csv|summary|kanji-readings|minimal-pairs|reading-index (json-lines by default)
+	--format sqlite <output.sqlite> (with the sqlite feature)
+	--predict-accents
+	--inline-warnings
+	--contains-kanji <kanji>
+	--defer-redirects <info.json> <redirects.json> (write the extraction without resolving its redirects)
+	--sentences <output.tsv> (write the example sentences of usage examples)
+	--stream (with --format json-lines or json-array only)";
+
+fn usage() -> ! {
+	eprintln!("{USAGE}");
+	std::process::exit(2);
 }
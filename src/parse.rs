@@ -1,19 +1,67 @@
-use crate::wikitext::TemplateParameters;
+use serde::{Deserialize, Serialize};
 
+use crate::{
+	infer::Dialect,
+	ja::{count_ideographs, is_ideograph, try_katakanify},
+	wikitext::{FindTemplates, NamedParameters, Param, decode_entities, remove_links},
+};
+
+#[derive(Serialize, Deserialize)]
 pub struct JaKanjitab {
 	pub readings: Vec<(String, u8)>,
-	pub alterations: Vec<Option<String>>,
+	pub alterations: Vec<Option<String>>, // Sound-changed readings (k1=), by kanji.
+	// Okurigana omitted from the title (o1=), by the kanji they follow. Okurigana written in the title need no
+	// parameter, and the template has no other spelling for them.
 	pub omissions: Vec<Option<String>>,
+	pub reference: Option<String>, // The cited source of the readings, if given.
+	pub yomi: Option<Yomi>,
+}
+
+// The class of the readings of the kanji of a compound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Yomi {
+	On,        // On'yomi throughout (e.g. 学期).
+	Kun,       // Kun'yomi throughout (e.g. 手紙).
+	Yutou,     // Kun'yomi then on'yomi (e.g. 湯桶).
+	Juubako,   // On'yomi then kun'yomi (e.g. 重箱).
+	Irregular, // Readings of the whole compound (e.g. jukujikun).
+}
+
+impl Yomi {
+	// The class of the reading of the i-th of the given number of kanji, if the yomi determines it.
+	pub fn of_kanji(self, i: usize, count: usize) -> Option<Self> {
+		match (self, count) {
+			(Self::On | Self::Kun, _) => Some(self),
+			(Self::Yutou, 2) => Some(if i == 0 { Self::Kun } else { Self::On }),
+			(Self::Juubako, 2) => Some(if i == 0 { Self::On } else { Self::Kun }),
+			_ => None,
+		}
+	}
+
+	// Parse the value of a yomi parameter, which may be abbreviated.
+	fn parse(value: &str) -> Option<Self> {
+		match value {
+			"on" | "o" | "goon" | "kanon" | "toon" | "kanyoon" => Some(Self::On),
+			"kun" | "k" => Some(Self::Kun),
+			"yutou" | "yu" => Some(Self::Yutou),
+			"juubako" | "ju" => Some(Self::Juubako),
+			"irregular" | "irr" | "i" | "jukujikun" => Some(Self::Irregular),
+			_ => None,
+		}
+	}
 }
 
 pub fn parse_ja_kanjitab(arguments: &str) -> Option<JaKanjitab> {
 	let mut readings = Vec::new();
 	let mut alterations = Vec::new();
 	let mut omissions = Vec::new();
+	let mut reference = None;
+	let mut yomi = None;
 
-	for argument in TemplateParameters::new(arguments) {
-		if let Some((parameter, value)) = argument.split_once('=') {
-			match parse_ja_kanjitab_parameter(parameter) {
+	for parameter in NamedParameters::new(arguments) {
+		match parameter {
+			Param::Named(parameter, value) => match parse_ja_kanjitab_parameter(&parameter) {
 				None => continue,
 				Some(JaKanjitabParameter::Alter(n)) => {
 					let n = n.strict_sub(1) as usize;
@@ -21,7 +69,7 @@ pub fn parse_ja_kanjitab(arguments: &str) -> Option<JaKanjitab> {
 						alterations.resize(n + 1, None);
 					}
 					assert!(alterations[n].is_none());
-					alterations[n] = Some(value.to_owned());
+					alterations[n] = Some(value.into_owned());
 				},
 				Some(JaKanjitabParameter::Omit(n)) => {
 					let n = n.strict_sub(1) as usize;
@@ -29,20 +77,18 @@ pub fn parse_ja_kanjitab(arguments: &str) -> Option<JaKanjitab> {
 						omissions.resize(n + 1, None);
 					}
 					assert!(omissions[n].is_none());
-					omissions[n] = Some(value.to_owned());
+					omissions[n] = Some(value.into_owned());
 				},
-				Some(JaKanjitabParameter::Reading(n)) => {
-					assert!(n as usize > readings.len());
-					readings.resize(n.strict_sub(1) as usize, ("".to_owned(), 1));
-					let (reading, count) = cut(value, |c: char| c.is_ascii_digit());
-					let count = if count.is_empty() { 1 } else { count.parse().unwrap() };
-					readings.push((reading.to_string(), count));
-				},
-			}
-		} else {
-			let (reading, count) = cut(&argument, |c: char| c.is_ascii_digit());
-			let count = if count.is_empty() { 1 } else { count.parse().unwrap() };
-			readings.push((reading.to_string(), count));
+				Some(JaKanjitabParameter::Reference) => reference = Some(value.into_owned()),
+				Some(JaKanjitabParameter::Yomi) => yomi = Yomi::parse(value.trim()),
+			},
+			// NOTE: 龍卷 has numbered readings.
+			Param::Positional(n, value) => {
+				let value = decode_entities(&value);
+				let (reading, count) = cut(&value, |c: char| c.is_ascii_digit());
+				let count = if count.is_empty() { 1 } else { count.parse().unwrap() };
+				set_positional(&mut readings, n, (reading.to_owned(), count), ("".to_owned(), 1));
+			},
 		}
 	}
 
@@ -51,17 +97,22 @@ pub fn parse_ja_kanjitab(arguments: &str) -> Option<JaKanjitab> {
 		return None;
 	}
 
-	Some(JaKanjitab { readings, alterations, omissions })
+	Some(JaKanjitab { readings, alterations, omissions, reference, yomi })
 }
 
 enum JaKanjitabParameter {
 	Alter(u8),
 	Omit(u8),
-	Reading(u8),
+	Reference,
+	Yomi,
 }
 
 fn parse_ja_kanjitab_parameter(parameter: &str) -> Option<JaKanjitabParameter> {
-	if let Some(number) = parameter.strip_prefix("k")
+	if matches!(parameter, "ref" | "kref") {
+		Some(JaKanjitabParameter::Reference)
+	} else if parameter == "yomi" {
+		Some(JaKanjitabParameter::Yomi)
+	} else if let Some(number) = parameter.strip_prefix("k")
 		&& number.chars().all(|x| x.is_ascii_digit())
 	{
 		Some(JaKanjitabParameter::Alter(if number.is_empty() { 1 } else { number.parse::<u8>().unwrap() }))
@@ -69,95 +120,351 @@ fn parse_ja_kanjitab_parameter(parameter: &str) -> Option<JaKanjitabParameter> {
 		&& number.chars().all(|x| x.is_ascii_digit())
 	{
 		Some(JaKanjitabParameter::Omit(if number.is_empty() { 1 } else { number.parse::<u8>().unwrap() }))
-	} else if !parameter.is_empty() && parameter.chars().all(|x| x.is_ascii_digit()) {
-		// NOTE: 龍卷.
-		Some(JaKanjitabParameter::Reading(parameter.parse::<u8>().unwrap()))
 	} else {
 		None
 	}
 }
 
+// Extract the readings of a compound template (e.g. {{ja-compound|手|て|紙|がみ}}) as a kanjitab would give
+// them, with one reading spanning the kanji of each component and any kana around them removed from it.
+// Returns None if a component has kana between kanji, or kana that its reading does not, or if the kanji of the
+// components do not spell those of the title (e.g. 国連 from 国際 and 連合).
+pub fn parse_ja_compound(title: &str, arguments: &str) -> Option<JaKanjitab> {
+	let mut positionals = Vec::new();
+	for parameter in NamedParameters::trimmed(arguments) {
+		if let Param::Positional(n, value) = parameter {
+			set_positional(&mut positionals, n, remove_links(&decode_entities(&value)), String::new());
+		}
+	}
+
+	let mut readings = Vec::new();
+	let mut ideographs = String::new();
+	for pair in positionals.chunks(2) {
+		let [component, reading] = pair else { return None };
+		let start = component.len() - component.trim_start_matches(|c| !is_ideograph(c)).len();
+		let end = component.trim_end_matches(|c| !is_ideograph(c)).len().max(start);
+		let (prefix, kanji, suffix) = (&component[..start], &component[start..end], &component[end..]);
+		if kanji.is_empty() {
+			continue;
+		} else if !kanji.chars().all(is_ideograph) {
+			return None;
+		}
+		let kana = |x: &str| try_katakanify(x, |c| c.is_whitespace(), |_| false);
+		let reading = kana(reading)?;
+		let reading = reading.strip_prefix(&kana(prefix)?)?.strip_suffix(&kana(suffix)?)?;
+		if reading.is_empty() {
+			return None;
+		}
+		readings.push((reading.to_owned(), count_ideographs(kanji) as u8));
+		ideographs.push_str(kanji);
+	}
+
+	if readings.is_empty() || !ideographs.chars().eq(title.chars().filter(|&c| is_ideograph(c))) {
+		return None;
+	}
+
+	Some(JaKanjitab { readings, alterations: Vec::new(), omissions: Vec::new(), reference: None, yomi: None })
+}
+
 pub struct JaPos {
+	pub pos: Option<String>, // The part of speech (e.g. noun), if known.
 	pub readings: Vec<String>,
+	pub spellings: Vec<String>, // The spellings given by head= or kanji=, if any.
 }
 
-// Extract a list of readings from a part-of-speech template.
-pub fn parse_ja_pos(is_generic: bool, arguments: &str) -> JaPos {
-	let mut handled_pos = false;
+// Extract a list of readings from a part-of-speech template, given its name.
+pub fn parse_ja_pos(name: &str, arguments: &str) -> JaPos {
+	// Generic templates give the part of speech as the first parameter, and others by their name.
+	let is_generic = name == "ja-pos";
+	let mut pos = name.strip_prefix("ja-").filter(|_| !is_generic).map(str::to_owned);
 	let mut readings = Vec::new();
-	for parameter in TemplateParameters::new(arguments) {
-		if let Some((parameter, value)) = parameter.split_once('=') {
-			if parameter.chars().all(|x| x.is_ascii_digit()) {
-				assert_eq!(parameter.parse::<u8>().unwrap() as usize, readings.len() + 1);
-				readings.push(value.to_owned());
-			}
-		} else if is_generic && !handled_pos {
-			handled_pos = true;
-		} else {
-			readings.push(parameter.into_owned());
+	let mut spellings = Vec::new();
+	for parameter in NamedParameters::new(arguments) {
+		match parameter {
+			Param::Positional(1, value) if is_generic => {
+				pos = Some(value.trim().to_owned()).filter(|x| !x.is_empty())
+			},
+			Param::Positional(n, value) => {
+				let n = if is_generic { n - 1 } else { n };
+				set_positional(&mut readings, n, decode_entities(&value).into_owned(), String::new());
+			},
+			Param::Named(parameter, value) if matches!(parameter.trim(), "head" | "kanji") => {
+				let spelling = remove_links(value.trim());
+				if !spelling.is_empty() {
+					spellings.push(spelling);
+				}
+			},
+			Param::Named(..) => continue,
 		}
 	}
-	JaPos { readings }
+	JaPos { pos, readings, spellings }
 }
 
+// Extract the alternative readings of an alternative-reading template, in hiragana (hira=) or katakana (kata=).
 pub fn parse_ja_altread(arguments: &str) -> JaPos {
 	let mut readings = Vec::new();
-	for parameter in TemplateParameters::new(arguments) {
-		if let Some((parameter, value)) = parameter.split_once('=')
-			&& parameter == "hira"
+	for parameter in NamedParameters::new(arguments) {
+		if let Param::Named(parameter, value) = parameter
+			&& matches!(parameter.as_ref(), "hira" | "kata")
 		{
-			readings.push(value.to_owned());
+			readings.push(decode_entities(&value).into_owned());
 		}
 	}
-	JaPos { readings }
+	JaPos { pos: None, readings, spellings: Vec::new() }
+}
+
+pub struct JaUsex {
+	pub japanese: String,
+	pub reading: Option<String>,
+	pub romaji: Option<String>, // A romanization given by tr=, if any.
+	pub translation: Option<String>,
+	pub literal: Option<String>, // A literal translation given by lit=, if any.
+}
+
+// Extract the example text, its kana reading, and its translations from a usage-example template.
+pub fn parse_ja_usex(arguments: &str) -> JaUsex {
+	let mut positionals = Vec::new();
+	let mut romaji = None;
+	let mut translation = None;
+	let mut literal = None;
+	for parameter in NamedParameters::new(arguments) {
+		match parameter {
+			Param::Positional(n, value) => {
+				set_positional(&mut positionals, n, value.into_owned(), String::new())
+			},
+			Param::Named(parameter, value) => {
+				let value = Some(remove_links(value.trim())).filter(|x| !x.is_empty());
+				match parameter.trim() {
+					"tr" => romaji = value,
+					"t" | "trans" | "translation" => translation = value,
+					"lit" => literal = value,
+					_ => continue,
+				}
+			},
+		}
+	}
+	let mut positionals = positionals.into_iter();
+	let japanese = remove_links(&positionals.next().unwrap_or_default());
+	let reading = positionals.next().filter(|x| !x.is_empty()).map(|x| remove_links(&x));
+	let translation =
+		translation.or_else(|| positionals.next().map(|x| remove_links(x.trim())).filter(|x| !x.is_empty()));
+	JaUsex { japanese, reading, romaji, translation, literal }
+}
+
+pub struct JaLink {
+	pub word: String,
+	pub reading: Option<String>,
+	pub gloss: Option<String>,
+}
+
+// Extract the linked word from an inline link template, given its name.
+// General link templates give the language code first, and are ignored unless it is Japanese.
+pub fn parse_ja_link(name: &str, arguments: &str) -> Option<JaLink> {
+	let offset = match name {
+		"l" | "ll" => 1,
+		"ja-r" | "ja-l" => 0,
+		_ => return None,
+	};
+	let mut positionals = Vec::new();
+	let mut gloss = None;
+	for parameter in NamedParameters::trimmed(arguments) {
+		match parameter {
+			Param::Named(parameter, value) if matches!(parameter.as_ref(), "t" | "gloss") => {
+				gloss = Some(value.into_owned())
+			},
+			Param::Named(..) => continue,
+			Param::Positional(n, value) => {
+				set_positional(&mut positionals, n, value.into_owned(), String::new())
+			},
+		}
+	}
+	if offset == 1 && positionals.first().is_none_or(|x| x != "ja") {
+		return None;
+	}
+	let mut positionals = positionals.into_iter().skip(offset).map(|x| Some(x).filter(|x| !x.is_empty()));
+	let word = remove_links(&positionals.next().flatten()?);
+	let reading = positionals.next().flatten().map(|x| remove_links(&decode_entities(&x)));
+	let gloss = gloss.or_else(|| positionals.next().flatten()).filter(|x| !x.is_empty());
+	Some(JaLink { word, reading, gloss })
 }
 
 pub struct JaPron {
 	pub readings: Vec<String>,
+	pub qualifiers: Vec<Vec<String>>, // The qualifiers of each reading (e.g. dated).
 	pub accents: Vec<JaPronAccent>,
-	pub accent_locations: Vec<bool>,
+	pub accent_locations: Vec<Option<String>>, // The regions of non-Tokyo accents, by accent.
+	pub audio: Vec<(usize, String)>,           // Audio filenames, each with the 1-based index of its reading.
+	pub accent_references: Vec<(usize, AccentReference)>, // Sources, each with the 1-based index of its accent.
+	pub devoicing: Vec<(usize, Vec<usize>)>, // Devoiced mora positions, each with the 1-based index of its reading.
+	pub is_yougen: bool,                     // Whether the word is conjugable (a verb or adjective).
+}
+
+impl JaPron {
+	// The dialect of the accent at a 0-based index.
+	pub fn dialect(&self, i: usize) -> Dialect {
+		match self.accent_locations.get(i) {
+			Some(Some(location)) => Dialect::from_location(location),
+			_ => Dialect::Tokyo,
+		}
+	}
+
+	// The accents given in a dialect, each with the 1-based index of its reading.
+	pub fn accents_for(&self, dialect: &Dialect) -> Vec<(usize, &JaPronAccent)> {
+		self.accents
+			.iter()
+			.enumerate()
+			.filter(|(i, accent)| !matches!(accent, JaPronAccent::None) && self.dialect(*i) == *dialect)
+			.map(|(i, accent)| (i + 1, accent))
+			.collect()
+	}
+}
+
+// The cited source of an accent.
+#[derive(Debug, Clone)]
+pub enum AccentReference {
+	Dictionary(String), // A reference template (e.g. {{R:ja:Daijirin}}), by the name of its dictionary.
+	Raw(String),        // Anything else, as given.
+}
+
+// Resolve the reference templates of an accent citation, keeping the citation as is if there are none.
+fn parse_accent_references(value: &str) -> Vec<AccentReference> {
+	let references: Vec<_> = FindTemplates::new(value)
+		.map_while(Result::ok)
+		.filter_map(|(name, arguments)| {
+			let dictionary = if name == "R:ja" {
+				NamedParameters::trimmed(arguments).find_map(|parameter| match parameter {
+					Param::Positional(1, value) => Some(value.into_owned()),
+					_ => None,
+				})?
+			} else {
+				name.strip_prefix("R:ja:").or_else(|| name.strip_prefix("R:"))?.to_owned()
+			};
+			Some(AccentReference::Dictionary(dictionary))
+		})
+		.collect();
+	if references.is_empty() && !value.is_empty() {
+		return vec![AccentReference::Raw(value.to_owned())];
+	}
+	references
+}
+
+#[derive(Debug)]
+pub enum JaPronParseError {
+	InvalidAccent(String),       // An accent that is neither a number nor one of h, a, and o.
+	UnknownAccentSuffix(String), // An accent parameter with an unrecognized suffix (e.g. acc_xyz).
+	InvalidIndex(String),        // A parameter whose index is not a positive number that fits in a u8.
 }
 
-pub fn parse_ja_pron(arguments: &str) -> Option<JaPron> {
+pub fn parse_ja_pron(arguments: &str) -> Result<JaPron, JaPronParseError> {
 	let mut readings = Vec::new();
+	let mut qualifiers = Vec::new();
 	let mut accents = Vec::new();
 	let mut accent_locations = Vec::new();
+	let mut audio = Vec::new();
+	let mut accent_references = Vec::new();
+	let mut devoicing = Vec::new();
+	let mut named_reading = None;
+	let mut is_yougen = false;
 
-	for argument in TemplateParameters::new(arguments) {
-		if let Some((parameter, value)) = argument.split_once('=') {
-			match parse_ja_pron_parameter(parameter) {
+	for parameter in NamedParameters::trimmed(arguments) {
+		match parameter {
+			Param::Named(parameter, value) => match parse_ja_pron_parameter(&parameter)? {
 				None => continue,
-				Some(JaPronParameter::Reading(n)) => {
-					assert_eq!(n as usize, readings.len() + 1);
-					readings.push(value.to_owned());
-				},
 				Some(JaPronParameter::Accent(n)) => {
 					let n = n.strict_sub(1) as usize;
 					if n >= accents.len() {
 						accents.resize(n + 1, JaPronAccent::None);
 					}
-					accents[n] = match value {
+					accents[n] = match value.as_ref() {
 						"h" => JaPronAccent::Numeric(0),
 						"a" => JaPronAccent::Numeric(1),
 						"o" => JaPronAccent::Odaka,
 						"" => JaPronAccent::None,
-						n => JaPronAccent::Numeric(n.parse::<u8>().unwrap()),
+						n => JaPronAccent::Numeric(
+							n.parse::<u8>().map_err(|_| JaPronParseError::InvalidAccent(n.to_owned()))?,
+						),
 					};
 				},
 				Some(JaPronParameter::Location(n)) => {
-					let n = n.strict_sub(1) as usize;
-					if n >= accent_locations.len() {
-						accent_locations.resize(n + 1, false);
+					// An empty location is left unset, as if not given.
+					let location = Some(value.into_owned()).filter(|x| !x.trim().is_empty());
+					set_positional(&mut accent_locations, n as usize, location, None)
+				},
+				Some(JaPronParameter::Audio(n)) => audio.push((n as usize, value.into_owned())),
+				Some(JaPronParameter::Reference(n)) => accent_references
+					.extend(parse_accent_references(&value).into_iter().map(|x| (n as usize, x))),
+				Some(JaPronParameter::Devoicing(n)) => {
+					let positions = value.split(',').filter_map(|x| x.trim().parse().ok()).collect();
+					devoicing.push((n as usize, positions));
+				},
+				Some(JaPronParameter::Yougen) => is_yougen = !matches!(value.as_ref(), "" | "0"),
+				// NOTE: A numeric y= flags the word as conjugable.
+				Some(JaPronParameter::Reading)
+					if parameter == "y" && value.chars().all(|c| c.is_ascii_digit()) =>
+				{
+					is_yougen = !matches!(value.as_ref(), "" | "0")
+				},
+				Some(JaPronParameter::Reading) => {
+					// NOTE: These parameters usually classify the reading instead (e.g. "y=on").
+					let value = decode_entities(&value);
+					if try_katakanify(&value, |c| matches!(c, '.' | '-') || c.is_whitespace(), |_| false)
+						.is_some()
+					{
+						named_reading = Some(value.into_owned());
 					}
-					accent_locations[n] = true;
 				},
-			}
-		} else {
-			readings.push(argument.to_string());
+			},
+			Param::Positional(n, value) => {
+				let value = decode_entities(&value);
+				let (reading, reading_qualifiers) = split_qualifiers(&value);
+				set_positional(&mut readings, n, reading.to_owned(), String::new());
+				set_positional(&mut qualifiers, n, reading_qualifiers, Vec::new());
+			},
 		}
 	}
 
-	Some(JaPron { readings, accents, accent_locations })
+	// An older convention gives the reading by name rather than by position.
+	if let Some(reading) = named_reading
+		&& readings.first().is_none_or(String::is_empty)
+	{
+		set_positional(&mut readings, 1, reading, String::new());
+	}
+
+	Ok(JaPron {
+		readings,
+		qualifiers,
+		accents,
+		accent_locations,
+		audio,
+		accent_references,
+		devoicing,
+		is_yougen,
+	})
+}
+
+// Split the inline modifiers (e.g. <q:dated>) and references off the end of a reading, returning the reading
+// and the qualifiers given by any q or qq modifiers, in order.
+fn split_qualifiers(reading: &str) -> (&str, Vec<String>) {
+	let mut reading = reading.trim_end();
+	let mut qualifiers = Vec::new();
+	loop {
+		if let Some(rest) = reading.strip_suffix("</ref>").or_else(|| reading.strip_suffix("/>"))
+			&& let Some(start) = rest.rfind("<ref")
+		{
+			reading = rest[..start].trim_end();
+		} else if let Some(rest) = reading.strip_suffix('>')
+			&& let Some(start) = rest.rfind('<')
+			&& let Some(qualifier) =
+				rest[start + 1..].strip_prefix("q:").or_else(|| rest[start + 1..].strip_prefix("qq:"))
+		{
+			qualifiers.push(qualifier.trim().to_owned());
+			reading = rest[..start].trim_end();
+		} else {
+			break;
+		}
+	}
+	qualifiers.reverse();
+	(reading, qualifiers)
 }
 
 #[derive(Clone)]
@@ -168,28 +475,236 @@ pub enum JaPronAccent {
 }
 
 enum JaPronParameter {
-	Reading(u8),
 	Accent(u8),
 	Location(u8),
+	Audio(u8),
+	Reference(u8),
+	Reading,
+	Devoicing(u8),
+	Yougen,
 }
 
-fn parse_ja_pron_parameter(parameter: &str) -> Option<JaPronParameter> {
-	if let Some(remainder) = parameter.strip_prefix("accent").or_else(|| parameter.strip_prefix("acc")) {
+fn parse_ja_pron_parameter(parameter: &str) -> Result<Option<JaPronParameter>, JaPronParseError> {
+	let parameter = if matches!(parameter, "y" | "yomi") {
+		JaPronParameter::Reading
+	} else if parameter == "yougen" {
+		JaPronParameter::Yougen
+	} else if let Some(remainder) = parameter.strip_prefix("accent").or_else(|| parameter.strip_prefix("acc"))
+	{
 		let (number, tail) = cut(remainder, |c: char| !c.is_numeric());
-		let number = if number.is_empty() { 1 } else { number.parse::<u8>().unwrap() };
+		let number = parse_index(parameter, number)?;
 		match tail {
-			"" => Some(JaPronParameter::Accent(number)),
-			"_ref" | "_note" => None,
-			"_loc" => Some(JaPronParameter::Location(number)),
-			_ => unimplemented!(),
+			"" => JaPronParameter::Accent(number),
+			"_ref" => JaPronParameter::Reference(number),
+			"_note" => return Ok(None),
+			"_loc" => JaPronParameter::Location(number),
+			_ => return Err(JaPronParseError::UnknownAccentSuffix(parameter.to_owned())),
 		}
-	} else if parameter.chars().all(|x| x.is_ascii_digit()) {
-		Some(JaPronParameter::Reading(parameter.parse::<u8>().unwrap()))
+	} else if let Some(number) = parameter.strip_prefix("dev")
+		&& number.chars().all(|x| x.is_ascii_digit())
+	{
+		JaPronParameter::Devoicing(parse_index(parameter, number)?)
+	} else if let Some(number) = parameter.strip_prefix("audio").or_else(|| parameter.strip_prefix("a"))
+		&& number.chars().all(|x| x.is_ascii_digit())
+	{
+		JaPronParameter::Audio(parse_index(parameter, number)?)
 	} else {
-		None
+		return Ok(None);
+	};
+	Ok(Some(parameter))
+}
+
+// Parse the 1-based index of a parameter, which is 1 if omitted.
+fn parse_index(parameter: &str, number: &str) -> Result<u8, JaPronParseError> {
+	if number.is_empty() {
+		return Ok(1);
+	}
+	number.parse().ok().filter(|x| *x > 0).ok_or_else(|| JaPronParseError::InvalidIndex(parameter.to_owned()))
+}
+
+// Store a value at a 1-based parameter position, padding any skipped positions.
+fn set_positional<T: Clone>(values: &mut Vec<T>, n: usize, value: T, padding: T) {
+	let n = n.strict_sub(1);
+	if n >= values.len() {
+		values.resize(n + 1, padding);
 	}
+	values[n] = value;
 }
 
 fn cut(text: &str, pattern: impl FnMut(char) -> bool) -> (&str, &str) {
 	text.split_at(text.find(pattern).unwrap_or(text.len()))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn accents_are_grouped_by_dialect() {
+		let ja_pron = parse_ja_pron("とうきょう|acc=0|acc2=2|acc2_loc=Kyoto").unwrap();
+		let tokyo = ja_pron.accents_for(&Dialect::Tokyo);
+		let keihan = ja_pron.accents_for(&Dialect::Keihan);
+		assert!(matches!(tokyo[..], [(1, JaPronAccent::Numeric(0))]));
+		assert!(matches!(keihan[..], [(2, JaPronAccent::Numeric(2))]));
+	}
+
+	#[test]
+	fn accent_locations_are_captured() {
+		let ja_pron = parse_ja_pron("おおさか|accent=0|accent_loc=Osaka|acc2=2").unwrap();
+		assert_eq!(ja_pron.accent_locations, [Some("Osaka".to_owned())]);
+		assert_eq!(ja_pron.dialect(0), Dialect::Keihan);
+		assert_eq!(ja_pron.dialect(1), Dialect::Tokyo);
+		let ja_pron = parse_ja_pron("おおさか|accent=0|accent_loc=|acc2=2|acc2_loc= ").unwrap();
+		assert_eq!(ja_pron.accent_locations, [None, None]);
+		assert_eq!((ja_pron.dialect(0), ja_pron.dialect(1)), (Dialect::Tokyo, Dialect::Tokyo));
+	}
+
+	#[test]
+	fn usage_examples_give_their_parts() {
+		let ja_usex =
+			parse_ja_usex("[[雨]] が [[降る]]|あめ が ふる|It rains.|tr=ame ga furu|lit=Rain falls.");
+		assert_eq!(ja_usex.japanese, "雨 が 降る");
+		assert_eq!(ja_usex.reading.as_deref(), Some("あめ が ふる"));
+		assert_eq!(ja_usex.romaji.as_deref(), Some("ame ga furu"));
+		assert_eq!(ja_usex.translation.as_deref(), Some("It rains."));
+		assert_eq!(ja_usex.literal.as_deref(), Some("Rain falls."));
+		assert_eq!(parse_ja_usex("雨|t=It rains.").translation.as_deref(), Some("It rains."));
+	}
+
+	#[test]
+	fn spaced_parameters_are_recognized() {
+		let ja_pron = parse_ja_pron(" あめ | acc = 1 ").unwrap();
+		assert_eq!(ja_pron.readings.len(), 1);
+		assert!(matches!(ja_pron.accents[..], [JaPronAccent::Numeric(1)]));
+	}
+
+	#[test]
+	fn values_keep_any_further_equals_signs() {
+		let ja_kanjitab = parse_ja_kanjitab("て|かみ|k2=が=み|ref=a=b").unwrap();
+		assert_eq!(ja_kanjitab.alterations, [None, Some("が=み".to_owned())]);
+		assert_eq!(ja_kanjitab.reference.as_deref(), Some("a=b"));
+	}
+
+	#[test]
+	fn japanese_links_give_their_word_and_reading() {
+		let link = |name, arguments| {
+			parse_ja_link(name, arguments).map(|x| (x.word, x.reading, x.gloss.unwrap_or_default()))
+		};
+		let tokyo = |gloss: &str| Some(("東京".to_owned(), Some("とうきょう".to_owned()), gloss.to_owned()));
+		assert_eq!(link("l", "ja|東京|とうきょう|Tokyo"), tokyo("Tokyo"));
+		assert_eq!(link("ll", "ja|[[東京]]|とうきょう|t=Tokyo"), tokyo("Tokyo"));
+		assert_eq!(link("ja-r", "東京|とうきょう"), tokyo(""));
+		assert_eq!(link("ja-l", "東京|とうきょう|gloss=Tokyo"), tokyo("Tokyo"));
+		assert_eq!(link("l", "en|Tokyo"), None);
+		assert_eq!(link("m", "ja|東京"), None);
+		assert_eq!(link("ja-r", "東京"), Some(("東京".to_owned(), None, String::new())));
+	}
+
+	#[test]
+	fn reference_templates_give_their_dictionary() {
+		let ja_pron =
+			parse_ja_pron("あめ|acc=1|acc_ref={{R:ja:Daijirin}}{{R:ja|NHK}}|acc2=0|acc2_ref=my notes")
+				.unwrap();
+		let references: Vec<_> = ja_pron
+			.accent_references
+			.iter()
+			.map(|(n, reference)| match reference {
+				AccentReference::Dictionary(x) => (*n, "dictionary", x.as_str()),
+				AccentReference::Raw(x) => (*n, "raw", x.as_str()),
+			})
+			.collect();
+		assert_eq!(
+			references,
+			[(1, "dictionary", "Daijirin"), (1, "dictionary", "NHK"), (2, "raw", "my notes")]
+		);
+	}
+
+	#[test]
+	fn readings_may_be_given_by_name() {
+		assert_eq!(parse_ja_pron("yomi=あめ|acc=1").unwrap().readings, ["あめ"]);
+		assert_eq!(parse_ja_pron("y=あめ|acc=1").unwrap().readings, ["あめ"]);
+		// A positional reading takes precedence, and a classification is not a reading.
+		assert_eq!(parse_ja_pron("あめ|yomi=あま").unwrap().readings, ["あめ"]);
+		assert!(parse_ja_pron("y=on|acc=1").unwrap().readings.is_empty());
+	}
+
+	#[test]
+	fn conjugable_words_are_flagged() {
+		assert!(parse_ja_pron("たべる|y=1|acc=2").unwrap().is_yougen);
+		assert!(parse_ja_pron("たべる|yougen=1|acc=2").unwrap().is_yougen);
+		assert!(!parse_ja_pron("たべる|y=0|acc=2").unwrap().is_yougen);
+		assert!(!parse_ja_pron("あめ|acc=1").unwrap().is_yougen);
+	}
+
+	#[test]
+	fn audio_files_are_given_by_reading() {
+		let ja_pron = parse_ja_pron("とうきょう|a=Ja-Tokyo.ogg|2=ときょ|a2=Ja-Tokyo-2.ogg").unwrap();
+		assert_eq!(ja_pron.readings, ["とうきょう", "ときょ"]);
+		assert_eq!(ja_pron.audio, [(1, "Ja-Tokyo.ogg".to_owned()), (2, "Ja-Tokyo-2.ogg".to_owned())]);
+	}
+
+	#[test]
+	fn malformed_pronunciations_are_errors() {
+		assert!(
+			matches!(parse_ja_pron("あめ|acc=foo"), Err(JaPronParseError::InvalidAccent(x)) if x == "foo")
+		);
+		assert!(
+			matches!(parse_ja_pron("あめ|acc=300"), Err(JaPronParseError::InvalidAccent(x)) if x == "300")
+		);
+		assert!(
+			matches!(parse_ja_pron("あめ|acc_xyz=1"), Err(JaPronParseError::UnknownAccentSuffix(x)) if x == "acc_xyz")
+		);
+		assert!(
+			matches!(parse_ja_pron("あめ|acc0=1"), Err(JaPronParseError::InvalidIndex(x)) if x == "acc0")
+		);
+		assert!(parse_ja_pron("あめ|acc_note=x|foo=bar").is_ok());
+	}
+
+	#[test]
+	fn kanjitab_yomi_is_parsed() {
+		let yomi = |arguments| parse_ja_kanjitab(arguments).unwrap().yomi;
+		assert_eq!(yomi("ゆ|とう|yomi=yutou"), Some(Yomi::Yutou));
+		assert_eq!(yomi("じゅう|ばこ|yomi=ju"), Some(Yomi::Juubako));
+		assert_eq!(yomi("て|がみ|yomi=k"), Some(Yomi::Kun));
+		assert_eq!(yomi("て|がみ|yomi=xyz"), None);
+		assert_eq!(yomi("て|がみ"), None);
+	}
+
+	#[test]
+	fn parts_of_speech_are_named_by_template_or_parameter() {
+		let ja_pos = parse_ja_pos("ja-noun", "あめ|head=[[雨]]");
+		assert_eq!(
+			(ja_pos.pos.as_deref(), ja_pos.readings, ja_pos.spellings),
+			(Some("noun"), vec!["あめ".to_owned()], vec!["雨".to_owned()])
+		);
+		let ja_pos = parse_ja_pos("ja-pos", "verb|たべる|kanji=食べる");
+		assert_eq!(
+			(ja_pos.pos.as_deref(), ja_pos.readings, ja_pos.spellings),
+			(Some("verb"), vec!["たべる".to_owned()], vec!["食べる".to_owned()])
+		);
+		assert_eq!(parse_ja_pos("ja-pos", "").pos, None);
+	}
+
+	#[test]
+	fn alternative_readings_are_given_in_either_syllabary() {
+		let ja_pos = parse_ja_altread("hira=あめ|kata=アマ|t=rain");
+		assert_eq!(ja_pos.readings, ["あめ", "アマ"]);
+	}
+
+	#[test]
+	fn qualifiers_are_split_off_readings() {
+		let ja_pron = parse_ja_pron(
+			"トウキョウ<q:dated><qq:rare>|2=とうけい<ref>Daijirin</ref>|3=とーきょー<ref name=\"x\" />",
+		)
+		.unwrap();
+		assert_eq!(ja_pron.readings, ["トウキョウ", "とうけい", "とーきょー"]);
+		assert_eq!(ja_pron.qualifiers, [vec!["dated".to_owned(), "rare".to_owned()], Vec::new(), Vec::new()]);
+	}
+
+	#[test]
+	fn kanjitab_okurigana_are_given_by_omissions() {
+		let ja_kanjitab = parse_ja_kanjitab("おこな|o1=う|rk1=う|kr1=う").unwrap();
+		assert_eq!(ja_kanjitab.readings, [("おこな".to_owned(), 1)]);
+		assert_eq!(ja_kanjitab.omissions, [Some("う".to_owned())]);
+	}
+}
@@ -0,0 +1,128 @@
+use std::{collections::HashMap, fmt, io, path::Path};
+
+use rusqlite::{Connection, params};
+
+use crate::{
+	WordInfo,
+	infer::{Atom, Dialect},
+};
+
+const SCHEMA: &str = "
+	CREATE TABLE words (title TEXT PRIMARY KEY);
+	CREATE TABLE readings (title TEXT, reading TEXT, PRIMARY KEY (title, reading));
+	CREATE TABLE accents (title TEXT, reading TEXT, nucleus INTEGER, dialect TEXT);
+	CREATE TABLE decomposition_atoms (
+		title TEXT,
+		reading TEXT,
+		idx INTEGER,
+		kind TEXT,
+		base TEXT,
+		atom_reading TEXT,
+		char_count INTEGER
+	);
+";
+
+#[derive(Debug)]
+pub enum DatabaseError {
+	Io(io::Error), // The existing file could not be replaced.
+	Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for DatabaseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DatabaseError::Io(e) => e.fmt(f),
+			DatabaseError::Sqlite(e) => e.fmt(f),
+		}
+	}
+}
+
+impl From<rusqlite::Error> for DatabaseError {
+	fn from(e: rusqlite::Error) -> Self {
+		DatabaseError::Sqlite(e)
+	}
+}
+
+// Write the inferred information to a new SQLite database, replacing any existing file.
+// NOTE: The reading of each atom is named atom_reading, as reading names the reading of the title.
+pub fn write_database(path: &Path, info: &HashMap<String, WordInfo>) -> Result<(), DatabaseError> {
+	match std::fs::remove_file(path) {
+		Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(DatabaseError::Io(e)),
+		_ => (),
+	}
+	let mut connection = Connection::open(path)?;
+	connection.execute_batch(SCHEMA)?;
+
+	let transaction = connection.transaction()?;
+	{
+		let mut insert_word = transaction.prepare("INSERT INTO words VALUES (?1)")?;
+		let mut insert_reading = transaction.prepare("INSERT INTO readings VALUES (?1, ?2)")?;
+		let mut insert_accent = transaction.prepare("INSERT INTO accents VALUES (?1, ?2, ?3, ?4)")?;
+		let mut insert_atom =
+			transaction.prepare("INSERT INTO decomposition_atoms VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+		for (title, word_info) in info {
+			insert_word.execute(params![title])?;
+			for (reading, reading_info) in &word_info.reading_infos {
+				insert_reading.execute(params![title, reading])?;
+				for accent in &reading_info.accents {
+					insert_accent.execute(params![title, reading, accent, Dialect::Tokyo.name()])?;
+				}
+				for (dialect, accent) in &reading_info.dialect_accents {
+					insert_accent.execute(params![title, reading, accent, dialect.name()])?;
+				}
+				let Some(decomposition) = &reading_info.decomposition else { continue };
+				for (i, atom) in decomposition.atoms.iter().enumerate() {
+					let (kind, base, atom_reading, character_count) = match atom {
+						Atom::Ruby { character_count, base, reading, .. } => {
+							("ruby", base.clone(), Some(reading.as_str()), Some(*character_count))
+						},
+						Atom::Unknown(c) => ("unknown", c.to_string(), None, None),
+						Atom::Kana(kana) => ("kana", kana.clone(), Some(kana.as_str()), None),
+					};
+					insert_atom.execute(params![
+						title,
+						reading,
+						i,
+						kind,
+						base,
+						atom_reading,
+						character_count
+					])?;
+				}
+			}
+		}
+	}
+	Ok(transaction.commit()?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::build_info;
+
+	#[test]
+	fn database_gives_the_accent_of_a_word() {
+		let text = "==Japanese==\n{{ja-pron|あめ|acc=1}}\n{{ja-noun|あめ}}\n";
+		let info = build_info([("雨".to_owned(), text.to_owned())].into_iter());
+		let path = std::env::temp_dir().join(format!("hatsuon-test-{}.sqlite", std::process::id()));
+		// Writing twice replaces the first database.
+		write_database(&path, &info).unwrap();
+		write_database(&path, &info).unwrap();
+		let connection = Connection::open(&path).unwrap();
+		let accents: Vec<(String, u8, String)> = connection
+			.prepare("SELECT reading, nucleus, dialect FROM accents WHERE title = '雨'")
+			.unwrap()
+			.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+			.unwrap()
+			.collect::<rusqlite::Result<_>>()
+			.unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(accents, [("アメ".to_owned(), 1, "tokyo".to_owned())]);
+	}
+
+	#[test]
+	fn unremovable_files_are_reported() {
+		let info = HashMap::new();
+		assert!(matches!(write_database(&std::env::temp_dir(), &info), Err(DatabaseError::Io(_))));
+	}
+}
@@ -0,0 +1,216 @@
+use std::{
+	collections::{HashMap, HashSet},
+	io::{self, Write},
+	mem,
+};
+
+use crate::{
+	Extraction, Extractor, Record, Redirect, Sentence, WordInfo, diagnostics::Diagnostics, process_page,
+	resolve_redirect,
+};
+
+// Writes the record of each title as JSON once it is final, rather than once every page is processed.
+// A title is final once its page is processed, unless it has a redirect, which must wait for the titles it
+// sees, up to the first one with a page. Only the readings of written titles are kept, for any later
+// redirects to them, so memory grows with the number of titles rather than with all of their information.
+// In exchange, a title can be held back until the end of the input by a redirect to a later one, and passes
+// that need every title at once (e.g. predict_accents) cannot be run.
+// NOTE: The records are written in the order that they become final, not sorted by title as in batch output,
+//       so the two only match once sorted. Sorting here would hold every record until the end.
+pub struct StreamingWriter<W: Write> {
+	output: W,
+	format: StreamFormat,
+	record_count: usize,
+	// The readings of each title whose page has been processed, if it has any information.
+	settled: HashMap<String, Option<HashSet<String>>>,
+	pending: HashMap<String, PendingPage>,
+	waiting: HashMap<String, Vec<String>>, // The pending titles waiting on each unsettled title.
+	pub sentences: Vec<Sentence>,
+}
+
+#[derive(Clone, Copy)]
+pub enum StreamFormat {
+	JsonLines, // A record per line.
+	JsonArray, // A record per line of a single array, closed by finish.
+}
+
+// A title whose redirects are waiting on titles whose pages have not yet been processed.
+struct PendingPage {
+	word_info: Option<WordInfo>,
+	redirects: Vec<Redirect>,
+}
+
+impl<W: Write> StreamingWriter<W> {
+	pub fn new(output: W, format: StreamFormat) -> Self {
+		Self {
+			output,
+			format,
+			record_count: 0,
+			settled: HashMap::new(),
+			pending: HashMap::new(),
+			waiting: HashMap::new(),
+			sentences: Vec::new(),
+		}
+	}
+
+	// Process a page, and write the record of each title that becomes final as a result.
+	pub fn process_page(
+		&mut self, title: &str, text: &str, extractor: &Extractor, diagnostics: &Diagnostics,
+	) -> io::Result<()> {
+		let mut extraction = Extraction::default();
+		process_page(title, text, &mut extraction, extractor, diagnostics);
+		self.sentences.append(&mut extraction.sentences);
+		let page = PendingPage { word_info: extraction.info.remove(title), redirects: extraction.redirects };
+		self.pending.insert(title.to_owned(), page);
+		self.advance(title.to_owned(), diagnostics)
+	}
+
+	// Write every remaining title, treating any title whose page was never processed as having none.
+	pub fn finish(mut self, diagnostics: &Diagnostics) -> io::Result<W> {
+		let mut titles: Vec<_> = self.pending.keys().cloned().collect();
+		titles.sort_unstable();
+		for title in titles {
+			if let Some(page) = self.pending.remove(&title) {
+				self.write(title, page, diagnostics)?;
+			}
+		}
+		if let StreamFormat::JsonArray = self.format {
+			let open = if self.record_count == 0 { "[" } else { "" };
+			writeln!(self.output, "{open}\n]")?;
+		}
+		self.output.flush()?;
+		Ok(self.output)
+	}
+
+	// Write the given pending title if it is final, and then any pending titles that it unblocks.
+	fn advance(&mut self, title: String, diagnostics: &Diagnostics) -> io::Result<()> {
+		let mut candidates = vec![title];
+		while let Some(title) = candidates.pop() {
+			let Some(page) = self.pending.get(&title) else { continue };
+			if let Some(blocker) = page.redirects.iter().find_map(|x| self.blocker(x)) {
+				self.waiting.entry(blocker.to_owned()).or_default().push(title);
+				continue;
+			}
+			let page = self.pending.remove(&title).unwrap();
+			candidates.extend(self.waiting.remove(&title).unwrap_or_default());
+			self.write(title, page, diagnostics)?;
+		}
+		Ok(())
+	}
+
+	// The first title seen by a redirect that is unsettled, unless an earlier one has information.
+	fn blocker<'a>(&self, redirect: &'a Redirect) -> Option<&'a str> {
+		for see in &redirect.sees {
+			match self.settled.get(see) {
+				Some(Some(_)) => return None,
+				Some(None) => continue,
+				None => return Some(see),
+			}
+		}
+		None
+	}
+
+	// Resolve the redirects of a title, settle it, and write its record if it has any inferred readings.
+	fn write(&mut self, title: String, page: PendingPage, diagnostics: &Diagnostics) -> io::Result<()> {
+		let mut info = HashMap::new();
+		if let Some(word_info) = page.word_info {
+			info.insert(title.clone(), word_info);
+		}
+		for redirect in page.redirects {
+			let readings = redirect.sees.iter().find_map(|see| self.settled.get(see).cloned().flatten());
+			if let Some(readings) = readings {
+				resolve_redirect(redirect, &readings, &mut info, diagnostics);
+			}
+		}
+		let Some(mut word_info) = info.remove(&title) else {
+			self.settled.insert(title, None);
+			return Ok(());
+		};
+		self.settled.insert(title.clone(), Some(mem::take(&mut word_info.readings)));
+
+		// Discard readings for which nothing was inferred, and finalize the rest, as in batch output.
+		word_info.finalize_accents();
+		if !word_info.remove_empty_readings(diagnostics) {
+			return Ok(());
+		}
+		match self.format {
+			StreamFormat::JsonLines => (),
+			StreamFormat::JsonArray => {
+				writeln!(self.output, "{}", if self.record_count == 0 { "[" } else { "," })?
+			},
+		}
+		self.record_count += 1;
+		serde_json::to_writer(&mut self.output, &Record::new(&title, &word_info))?;
+		match self.format {
+			StreamFormat::JsonLines => writeln!(self.output),
+			// NOTE: The separator after a record is only written once the next is final.
+			StreamFormat::JsonArray => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{AccentConflictPolicy, build_info, finalize_accents, remove_empty_readings};
+
+	const PAGES: [(&str, &str); 3] = [
+		("手帖", "==Japanese==\n{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手紙}}\n"),
+		(
+			"手紙",
+			"==Japanese==\n{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-pron|てがみ|acc=0}}\n{{ja-noun|てがみ}}\n",
+		),
+		("雨", "==Japanese==\n{{ja-pron|あめ|acc=1}}\n{{ja-noun|あめ}}\n"),
+	];
+
+	fn stream(pages: &[(&str, &str)], format: StreamFormat) -> String {
+		let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+		let diagnostics = Diagnostics::new();
+		let mut writer = StreamingWriter::new(Vec::new(), format);
+		for (title, text) in pages {
+			writer.process_page(title, text, &extractor, &diagnostics).unwrap();
+		}
+		String::from_utf8(writer.finish(&diagnostics).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn json_array_output_is_a_json_array() {
+		let output = stream(&PAGES, StreamFormat::JsonArray);
+		let records: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+		let titles: Vec<_> = records.iter().map(|x| x["title"].as_str().unwrap()).collect();
+		assert_eq!(titles, ["手紙", "手帖", "雨"]);
+		let empty: Vec<serde_json::Value> =
+			serde_json::from_str(&stream(&[], StreamFormat::JsonArray)).unwrap();
+		assert!(empty.is_empty());
+	}
+
+	#[test]
+	fn streamed_output_matches_batch_output_once_sorted() {
+		let mut streamed: Vec<_> =
+			stream(&PAGES, StreamFormat::JsonLines).lines().map(str::to_owned).collect();
+		streamed.sort_unstable();
+		let mut info = build_info(PAGES.iter().map(|(title, text)| (title.to_string(), text.to_string())));
+		finalize_accents(&mut info);
+		remove_empty_readings(&mut info, &Diagnostics::new());
+		let mut batch: Vec<_> = info
+			.iter()
+			.map(|(title, word_info)| serde_json::to_string(&Record::new(title, word_info)).unwrap())
+			.collect();
+		batch.sort_unstable();
+		assert_eq!(streamed, batch);
+	}
+
+	#[test]
+	fn readings_with_nothing_inferred_are_dropped() {
+		let pages = [("飴", "==Japanese==\n{{ja-pron|あめ}}\n{{ja-noun|あめ}}\n"), PAGES[2]];
+		// The reading of 飴 is found, but nothing is inferred for it, so its page has no record.
+		let info = build_info(pages.iter().map(|(title, text)| (title.to_string(), text.to_string())));
+		assert!(info["飴"].reading_infos.values().all(|x| x.is_empty()));
+		let output = stream(&pages, StreamFormat::JsonLines);
+		let titles: Vec<_> = output
+			.lines()
+			.map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap()["title"].clone())
+			.collect();
+		assert_eq!(titles, ["雨"]);
+	}
+}
@@ -1,74 +1,184 @@
+use std::{borrow::Cow, ops::Range};
+
 // Iterator over Wikitext templates {{...}} in a page.
 pub struct FindTemplates<'a> {
 	s: &'a str,
 	char_indices: std::str::CharIndices<'a>,
-	name: &'a str,
+	name: Cow<'a, str>,
+	template_start: usize,
+	outer_start: usize, // The byte offset of the outermost open brace construct.
 	start: usize,
-	depth: usize,
+	braces: Vec<u8>, // The widths of the enclosing brace constructs, from outermost to innermost.
+	max_depth: usize,
+	is_template: bool,
 	is_invalid: bool,
 }
 
+// The nesting limit of MediaWiki's template expansion.
+const DEFAULT_MAX_DEPTH: usize = 64;
+
 impl<'a> FindTemplates<'a> {
 	pub fn new(s: &'a str) -> Self {
-		Self { s, char_indices: s.char_indices(), name: "", start: 0, depth: 0, is_invalid: false }
+		Self::with_max_depth(s, DEFAULT_MAX_DEPTH)
+	}
+
+	// Stop with an error once brace constructs are nested more than a given number of levels deep.
+	pub fn with_max_depth(s: &'a str, max_depth: usize) -> Self {
+		Self {
+			s,
+			char_indices: s.char_indices(),
+			name: Cow::Borrowed(""),
+			template_start: 0,
+			outer_start: 0,
+			start: 0,
+			braces: Vec::new(),
+			max_depth,
+			is_template: false,
+			is_invalid: false,
+		}
+	}
+
+	// Also yield the byte range of each template, including its enclosing braces.
+	pub fn spanned(self) -> FindTemplatesSpanned<'a> {
+		FindTemplatesSpanned(self)
+	}
+
+	// Resume scanning just after the opening of each malformed construct, rather than stopping at the first.
+	pub fn recovering(self) -> FindTemplatesRecovering<'a> {
+		FindTemplatesRecovering { s: self.s, offset: 0, inner: self }
+	}
+}
+
+// Iterator over Wikitext templates {{...}} in a page, which yields an error for each malformed construct.
+pub struct FindTemplatesRecovering<'a> {
+	s: &'a str,
+	offset: usize, // The byte offset of the text being scanned, from which resumed errors are reported.
+	inner: FindTemplates<'a>,
+}
+
+impl<'a> Iterator for FindTemplatesRecovering<'a> {
+	type Item = Result<(Cow<'a, str>, &'a str), TemplateError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.inner.next()? {
+			Ok(template) => Some(Ok(template)),
+			Err(mut e) => {
+				e.offset += self.offset;
+				// NOTE: Skip the whole run of braces that opens or closes the offending construct, so that no
+				//       construct is reported twice. Each such run is ASCII, so this ends on a char boundary.
+				let c = self.s.as_bytes()[e.offset];
+				self.offset = e.offset + self.s[e.offset..].bytes().take_while(|&x| x == c).count();
+				self.inner = FindTemplates::with_max_depth(&self.s[self.offset..], self.inner.max_depth);
+				Some(Err(e))
+			},
+		}
 	}
 }
 
 impl<'a> Iterator for FindTemplates<'a> {
-	type Item = (&'a str, &'a str);
+	type Item = Result<(Cow<'a, str>, &'a str), TemplateError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_spanned().map(|x| x.map(|(name, arguments, _)| (name, arguments)))
+	}
+}
+
+#[derive(Debug)]
+pub struct TemplateError {
+	pub offset: usize, // The byte offset of the offending brace or comment.
+	pub kind: TemplateErrorKind,
+}
+
+#[derive(Debug)]
+pub enum TemplateErrorKind {
+	UnbalancedClose,
+	UnterminatedComment,
+	UnterminatedNowiki,
+	UnterminatedTemplate,
+	TooDeep,
+}
+
+// A template's name, its arguments, and the byte range of the whole template.
+pub type SpannedTemplate<'a> = (Cow<'a, str>, &'a str, Range<usize>);
+
+// Iterator over Wikitext templates {{...}} in a page, along with their byte ranges.
+pub struct FindTemplatesSpanned<'a>(FindTemplates<'a>);
+
+impl<'a> Iterator for FindTemplatesSpanned<'a> {
+	type Item = Result<SpannedTemplate<'a>, TemplateError>;
 
 	fn next(&mut self) -> Option<Self::Item> {
+		self.0.next_spanned()
+	}
+}
+
+impl<'a> FindTemplates<'a> {
+	fn fail<T>(&mut self, offset: usize, kind: TemplateErrorKind) -> Option<Result<T, TemplateError>> {
+		self.is_invalid = true;
+		Some(Err(TemplateError { offset, kind }))
+	}
+
+	fn next_spanned(&mut self) -> Option<Result<SpannedTemplate<'a>, TemplateError>> {
 		if self.is_invalid {
 			return None;
 		}
 
 		'outer: while let Some((i, c)) = self.char_indices.next() {
 			match c {
-				c @ '{' if try_consume(&mut self.char_indices, &[c, c]) => {
-					self.depth += 1;
-				},
-				c @ '}' if try_consume(&mut self.char_indices, &[c, c]) => {
-					let Some(depth) = self.depth.checked_sub(1) else {
-						self.is_invalid = true;
-						return None;
-					};
-					self.depth = depth;
-				},
-				c @ '{' if try_consume(&mut self.char_indices, &[c]) => {
-					if self.depth == 0 {
+				'{' if self.char_indices.clone().next().is_some_and(|(_, x)| x == '{') => {
+					let n = 1 + consume_run(&mut self.char_indices, '{');
+					if self.braces.is_empty() {
+						self.outer_start = i;
+					}
+					if self.braces.is_empty() && n == 2 {
 						// TODO: This is messy, but also likely incorrect. A more sound solution may be to
 						//       integrate this parser with the parameter separator parser.
-						let limit = self.s[i + 2..].find("}}");
-						let Some(end) = limit.and_then(|x| self.s[i + 2..i + 2 + x].find('|')) else {
-							let Some(end) = limit else {
-								self.is_invalid = true;
-								return None;
-							};
-							for _ in 0..self.s[i + 2..i + 2 + end].chars().count() + "}}".len() {
+						let Some((end, has_arguments)) = scan_template_name(&self.s[i + 2..]) else {
+							return self.fail(i, TemplateErrorKind::UnterminatedTemplate);
+						};
+						let raw_name = &self.s[i + 2..i + 2 + end];
+						let name = strip_comments(raw_name);
+						if !has_arguments {
+							for _ in 0..raw_name.chars().count() + "}}".len() {
 								self.char_indices.next();
 							}
 							let end = i + 2 + end;
-							let name = &self.s[i + 2..end];
 							self.start = end + 1;
-							return Some((name, ""));
-						};
-						for _ in 0..self.s[i + 2..i + 2 + end].chars().count() + '|'.len_utf8() {
+							if is_parser_function(&name) {
+								continue;
+							}
+							return Some(Ok((name, "", i..end + "}}".len())));
+						}
+						for _ in 0..raw_name.chars().count() + '|'.len_utf8() {
 							self.char_indices.next();
 						}
 						let end = i + 2 + end;
-						self.name = &self.s[i + 2..end];
+						self.is_template = !is_parser_function(&name);
+						self.name = name;
+						self.template_start = i;
 						self.start = end + 1;
 					}
-					self.depth += 1;
+					self.braces.extend(split_brace_run(n));
+					if self.braces.len() > self.max_depth {
+						return self.fail(i, TemplateErrorKind::TooDeep);
+					}
 				},
-				c @ '}' if try_consume(&mut self.char_indices, &[c]) => {
-					let Some(depth) = self.depth.checked_sub(1) else {
-						self.is_invalid = true;
-						return None;
-					};
-					self.depth = depth;
-					if self.depth == 0 {
-						return Some((self.name, &self.s[self.start..i]));
+				'}' if self.char_indices.clone().next().is_some_and(|(_, x)| x == '}') => {
+					if self.braces.is_empty() {
+						return self.fail(i, TemplateErrorKind::UnbalancedClose);
+					}
+					let (used, last_width) =
+						close_brace_run(&mut self.braces, 1 + count_run(&self.char_indices, '}'));
+					for _ in 1..used {
+						self.char_indices.next();
+					}
+					if self.braces.is_empty() && std::mem::take(&mut self.is_template) {
+						let end = i + used;
+						return Some(Ok((
+							std::mem::take(&mut self.name),
+							&self.s[self.start..end - last_width],
+							self.template_start..end,
+						)));
 					}
 				},
 				'<' if try_consume(&mut self.char_indices, &['!', '-', '-']) => {
@@ -77,13 +187,28 @@ impl<'a> Iterator for FindTemplates<'a> {
 							continue 'outer;
 						};
 					}
-					self.is_invalid = true;
-					return None;
+					return self.fail(i, TemplateErrorKind::UnterminatedComment);
+				},
+				'<' if try_consume(&mut self.char_indices, &['n', 'o', 'w', 'i', 'k', 'i']) => {
+					// NOTE: Self-closing tags (<nowiki/>) have no content to skip.
+					if !try_consume(&mut self.char_indices, &['>']) {
+						continue;
+					}
+					let Some(end) = self.s[i..].find("</nowiki>") else {
+						return self.fail(i, TemplateErrorKind::UnterminatedNowiki);
+					};
+					for _ in 0..self.s[i + "<nowiki>".len()..i + end].chars().count() + "</nowiki>".len() {
+						self.char_indices.next();
+					}
 				},
 				_ => (),
 			}
 		}
 
+		if !self.braces.is_empty() {
+			return self.fail(self.outer_start, TemplateErrorKind::UnterminatedTemplate);
+		}
+
 		None
 	}
 }
@@ -93,56 +218,91 @@ pub struct TemplateParameters<'a> {
 	s: &'a str,
 	char_indices: std::str::CharIndices<'a>,
 	start: usize,
-	depth: usize,
+	braces: Vec<u8>, // The widths of the enclosing brace constructs, from outermost to innermost.
+	links: usize,
 	commentless: Option<String>,
+	is_trimmed: bool,
 	is_invalid: bool,
 }
 
 impl<'a> TemplateParameters<'a> {
 	pub fn new(s: &'a str) -> Self {
-		Self { s, char_indices: s.char_indices(), start: 0, depth: 0, commentless: None, is_invalid: false }
+		Self {
+			s,
+			char_indices: s.char_indices(),
+			start: 0,
+			braces: Vec::new(),
+			links: 0,
+			commentless: None,
+			is_trimmed: false,
+			is_invalid: false,
+		}
+	}
+
+	// Strip surrounding whitespace from each parameter (e.g. "{{ja-pron | acc = 0 }}").
+	pub fn trimmed(s: &'a str) -> Self {
+		Self { is_trimmed: true, ..Self::new(s) }
 	}
 }
 
 impl<'a> Iterator for TemplateParameters<'a> {
-	type Item = std::borrow::Cow<'a, str>;
+	type Item = Cow<'a, str>;
 
 	fn next(&mut self) -> Option<Self::Item> {
+		let parameter = self.next_untrimmed()?;
+		Some(if self.is_trimmed { trim(parameter) } else { parameter })
+	}
+}
+
+fn trim(s: Cow<'_, str>) -> Cow<'_, str> {
+	match s {
+		Cow::Borrowed(s) => Cow::Borrowed(s.trim()),
+		Cow::Owned(s) if s.trim().len() == s.len() => Cow::Owned(s),
+		Cow::Owned(s) => Cow::Owned(s.trim().to_owned()),
+	}
+}
+
+impl<'a> TemplateParameters<'a> {
+	fn next_untrimmed(&mut self) -> Option<Cow<'a, str>> {
 		if self.is_invalid {
 			return None;
 		}
 
 		'outer: while let Some((i, c)) = self.char_indices.next() {
 			match c {
-				'|' if self.depth == 0 => {
+				'|' if self.braces.is_empty() && self.links == 0 => {
 					let part = &self.s[self.start..i];
 					self.start = i + 1;
 					if let Some(mut owned) = self.commentless.take() {
 						owned.push_str(part);
-						return Some(std::borrow::Cow::Owned(owned));
+						return Some(Cow::Owned(owned));
 					} else {
-						return Some(std::borrow::Cow::Borrowed(part));
+						return Some(Cow::Borrowed(part));
 					}
 				},
-				c @ '{' if try_consume(&mut self.char_indices, &[c, c]) => {
-					self.depth += 1;
+				'{' if self.char_indices.clone().next().is_some_and(|(_, x)| x == '{') => {
+					let n = 1 + consume_run(&mut self.char_indices, '{');
+					self.braces.extend(split_brace_run(n));
 				},
-				c @ '}' if try_consume(&mut self.char_indices, &[c, c]) => {
-					let Some(depth) = self.depth.checked_sub(1) else {
+				'}' if self.char_indices.clone().next().is_some_and(|(_, x)| x == '}') => {
+					if self.braces.is_empty() {
 						self.is_invalid = true;
 						return None;
-					};
-					self.depth = depth;
+					}
+					let (used, _) = close_brace_run(&mut self.braces, 1 + count_run(&self.char_indices, '}'));
+					for _ in 1..used {
+						self.char_indices.next();
+					}
 				},
-				c @ '[' | c @ '{' if try_consume(&mut self.char_indices, &[c]) => {
-					self.depth += 1;
+				c @ '[' if try_consume(&mut self.char_indices, &[c]) => {
+					self.links += 1;
 				},
-				c @ ']' | c @ '}' if try_consume(&mut self.char_indices, &[c]) => {
-					let Some(depth) = self.depth.checked_sub(1) else {
+				c @ ']' if try_consume(&mut self.char_indices, &[c]) => {
+					let Some(links) = self.links.checked_sub(1) else {
 						self.is_invalid = true;
 						return None;
 					};
-					self.depth = depth;
+					self.links = links;
 				},
 				'<' if try_consume(&mut self.char_indices, &['!', '-', '-']) => {
 					if let Some(has) = self.commentless.as_mut() {
@@ -168,9 +328,9 @@ impl<'a> Iterator for TemplateParameters<'a> {
 			self.start = self.s.len();
 			if let Some(mut owned) = self.commentless.take() {
 				owned.push_str(part);
-				return Some(std::borrow::Cow::Owned(owned));
+				return Some(Cow::Owned(owned));
 			} else {
-				return Some(std::borrow::Cow::Borrowed(part));
+				return Some(Cow::Borrowed(part));
 			}
 		}
 
@@ -178,6 +338,255 @@ impl<'a> Iterator for TemplateParameters<'a> {
 	}
 }
 
+// An argument of a Wikitext template, either positional or named.
+pub enum Arg<'a> {
+	Positional(Cow<'a, str>),
+	Named { key: Cow<'a, str>, value: Cow<'a, str> },
+}
+
+// Iterator over the arguments of a Wikitext template {{...|...|...}}, split into keys and values.
+// An argument is named if it contains an '=' before any nested link or template.
+pub struct TemplateArguments<'a>(TemplateParameters<'a>);
+
+impl<'a> TemplateArguments<'a> {
+	pub fn new(s: &'a str) -> Self {
+		Self(TemplateParameters::new(s))
+	}
+
+	// Strip surrounding whitespace from each argument, and from each key and value.
+	pub fn trimmed(s: &'a str) -> Self {
+		Self(TemplateParameters::trimmed(s))
+	}
+}
+
+impl<'a> Iterator for TemplateArguments<'a> {
+	type Item = Arg<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let argument = self.split_next()?;
+		Some(match argument {
+			Arg::Named { key, value } if self.0.is_trimmed => {
+				Arg::Named { key: trim(key), value: trim(value) }
+			},
+			argument => argument,
+		})
+	}
+}
+
+impl<'a> TemplateArguments<'a> {
+	fn split_next(&mut self) -> Option<Arg<'a>> {
+		Some(match self.0.next()? {
+			Cow::Borrowed(argument) => match split_argument(argument) {
+				Some((key, value)) => Arg::Named { key: Cow::Borrowed(key), value: Cow::Borrowed(value) },
+				None => Arg::Positional(Cow::Borrowed(argument)),
+			},
+			Cow::Owned(argument) => match split_argument(&argument) {
+				Some((key, value)) => {
+					Arg::Named { key: Cow::Owned(key.to_owned()), value: Cow::Owned(value.to_owned()) }
+				},
+				None => Arg::Positional(Cow::Owned(argument)),
+			},
+		})
+	}
+}
+
+// Split an argument into a name and value at its first '=', if it comes before any link or template.
+// The value keeps any further '=' (e.g. "k1=a=b" has the value "a=b").
+fn split_argument(argument: &str) -> Option<(&str, &str)> {
+	let i = argument.find(['=', '[', '{'])?;
+	(argument.as_bytes()[i] == b'=').then(|| (&argument[..i], &argument[i + 1..]))
+}
+
+// A parameter of a Wikitext template, numbered from 1 if positional.
+pub enum Param<'a> {
+	Named(Cow<'a, str>, Cow<'a, str>),
+	Positional(usize, Cow<'a, str>),
+}
+
+// Iterator over the parameters of a Wikitext template, split into names and values.
+// Explicitly numbered parameters (e.g. "2=...") are yielded as positional parameters.
+pub struct NamedParameters<'a> {
+	arguments: TemplateArguments<'a>,
+	position: usize,
+}
+
+impl<'a> NamedParameters<'a> {
+	pub fn new(s: &'a str) -> Self {
+		Self { arguments: TemplateArguments::new(s), position: 0 }
+	}
+
+	// Strip surrounding whitespace from each name and value.
+	pub fn trimmed(s: &'a str) -> Self {
+		Self { arguments: TemplateArguments::trimmed(s), position: 0 }
+	}
+}
+
+impl<'a> Iterator for NamedParameters<'a> {
+	type Item = Param<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.arguments.next()? {
+			Arg::Positional(value) => {
+				self.position += 1;
+				Some(Param::Positional(self.position, value))
+			},
+			Arg::Named { key, value } => {
+				if !key.is_empty()
+					&& key.chars().all(|x| x.is_ascii_digit())
+					&& let Ok(n) = key.parse::<usize>()
+					&& n > 0
+				{
+					Some(Param::Positional(n, value))
+				} else {
+					Some(Param::Named(key, value))
+				}
+			},
+		}
+	}
+}
+
+// A template whose parameter values are parsed into text and nested templates.
+#[derive(Debug)]
+pub struct TemplateNode {
+	pub name: String,
+	pub params: Vec<TemplateParam>,
+}
+
+#[derive(Debug)]
+pub enum TemplateParam {
+	Named(String, Vec<TemplateValue>),
+	Positional(usize, Vec<TemplateValue>),
+}
+
+#[derive(Debug)]
+pub enum TemplateValue {
+	Text(String),
+	Template(TemplateNode),
+}
+
+// Parse the templates of a string into trees, stopping at the first malformed template.
+// Unlike FindTemplates, this descends into parameter values, so it is best kept to small strings.
+pub fn parse_template_tree(s: &str) -> Vec<TemplateNode> {
+	let mut nodes = Vec::new();
+	for template in FindTemplates::new(s) {
+		let Ok((name, arguments)) = template else { break };
+		let params = NamedParameters::new(arguments)
+			.map(|param| match param {
+				Param::Named(key, value) => {
+					TemplateParam::Named(key.into_owned(), parse_template_value(&value))
+				},
+				Param::Positional(n, value) => TemplateParam::Positional(n, parse_template_value(&value)),
+			})
+			.collect();
+		nodes.push(TemplateNode { name: name.into_owned(), params });
+	}
+	nodes
+}
+
+// Split a parameter value into runs of text and the templates between them.
+fn parse_template_value(s: &str) -> Vec<TemplateValue> {
+	let mut values = Vec::new();
+	let mut end = 0;
+	for template in FindTemplates::new(s).spanned() {
+		let Ok((_, _, range)) = template else { break };
+		if end < range.start {
+			values.push(TemplateValue::Text(s[end..range.start].to_owned()));
+		}
+		values.extend(parse_template_tree(&s[range.clone()]).into_iter().map(TemplateValue::Template));
+		end = range.end;
+	}
+	if end < s.len() {
+		values.push(TemplateValue::Text(s[end..].to_owned()));
+	}
+	values
+}
+
+// Find the end of a template name, which may contain comments, and whether arguments follow it.
+// Braces in the name are balanced first, as the name of a parser function includes its first argument
+// (e.g. {{#if:{{{1|}}}|yes|no}}), whose pipes do not end the name.
+fn scan_template_name(s: &str) -> Option<(usize, bool)> {
+	let mut char_indices = s.char_indices();
+	let mut depth = 0usize;
+	while let Some((i, c)) = char_indices.next() {
+		match c {
+			'{' => depth += 1,
+			'}' if depth > 0 => depth -= 1,
+			'|' if depth == 0 => return Some((i, true)),
+			'}' if try_consume(&mut char_indices, &['}']) => return Some((i, false)),
+			'<' if try_consume(&mut char_indices, &['!', '-', '-']) => {
+				let end = s[i..].find("-->")?;
+				for _ in 0..s[i + "<!--".len()..i + end].chars().count() + "-->".len() {
+					char_indices.next();
+				}
+			},
+			_ => (),
+		}
+	}
+	None
+}
+
+// Remove comments from a template name, along with any surrounding whitespace.
+fn strip_comments(name: &str) -> Cow<'_, str> {
+	if !name.contains("<!--") {
+		return Cow::Borrowed(name.trim());
+	}
+	let mut stripped = String::with_capacity(name.len());
+	let mut rest = name;
+	while let Some(start) = rest.find("<!--") {
+		stripped.push_str(&rest[..start]);
+		let end = rest[start..].find("-->").map_or(rest.len(), |x| start + x + "-->".len());
+		rest = &rest[end..];
+	}
+	stripped.push_str(rest);
+	Cow::Owned(stripped.trim().to_owned())
+}
+
+// Parser functions (e.g. {{#if:...|...}}) are not templates, and their first argument is not delimited by a
+// pipe, so they are skipped.
+fn is_parser_function(name: &str) -> bool {
+	name.starts_with('#')
+}
+
+// Count the consecutive occurrences of a character at the front of an iterator, without consuming them.
+fn count_run(chars: &std::str::CharIndices, c: char) -> usize {
+	chars.clone().take_while(|(_, x)| *x == c).count()
+}
+
+fn consume_run(chars: &mut std::str::CharIndices, c: char) -> usize {
+	let n = count_run(chars, c);
+	for _ in 0..n {
+		chars.next();
+	}
+	n
+}
+
+// Split a run of at least two opening braces into the widths of the constructs it opens, from outermost
+// to innermost, where "{{" opens a template and "{{{" opens a parameter reference (e.g. "{{{1|default}}}").
+fn split_brace_run(n: usize) -> impl Iterator<Item = u8> {
+	let templates = match n % 3 {
+		0 => 0,
+		1 => 2,
+		_ => 1,
+	};
+	std::iter::repeat_n(2, templates).chain(std::iter::repeat_n(3, (n - 2 * templates) / 3))
+}
+
+// Close as many constructs as a run of closing braces allows, leaving any excess braces for later.
+// Returns the number of braces used and the width of the outermost construct closed.
+fn close_brace_run(braces: &mut Vec<u8>, run: usize) -> (usize, usize) {
+	let mut remaining = run;
+	let mut used = 0;
+	let mut last_width = 0;
+	while remaining >= 2
+		&& let Some(width) = braces.pop()
+	{
+		last_width = (width as usize).min(remaining);
+		used += last_width;
+		remaining -= last_width;
+	}
+	(used, last_width)
+}
+
 pub fn try_consume<'a>(chars: &mut std::str::CharIndices<'a>, peek: &[char]) -> bool {
 	let mut cs = chars.clone().map(|a| a.1);
 	for o in peek {
@@ -191,26 +600,492 @@ pub fn try_consume<'a>(chars: &mut std::str::CharIndices<'a>, peek: &[char]) ->
 	true
 }
 
-// Remove [[...]] and [[...|...]] from a string.
-pub fn remove_links(reading: &str) -> String {
-	let mut char_indices = reading.char_indices();
-	let mut buffer = String::new();
+// A section of a page, running from its heading to the next heading of the same or a higher level.
+pub struct Section<'a> {
+	pub level: u8,
+	pub title: &'a str,
+	pub body: &'a str, // The text after the heading line, including any subsections.
+}
+
+// Iterator over the sections of a page, from ==...== to ======...======, in order of appearance.
+// Headings inside comments and templates are ignored.
+pub struct Sections<'a> {
+	s: &'a str,
+	headings: Vec<Heading<'a>>,
+	index: usize,
+}
+
+struct Heading<'a> {
+	level: u8,
+	title: &'a str,
+	line_start: usize,
+	body_start: usize,
+}
+
+impl<'a> Sections<'a> {
+	pub fn new(s: &'a str) -> Self {
+		Self { s, headings: find_headings(s), index: 0 }
+	}
+}
+
+impl<'a> Iterator for Sections<'a> {
+	type Item = Section<'a>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let heading = self.headings.get(self.index)?;
+		self.index += 1;
+		let end = self.headings[self.index..]
+			.iter()
+			.find(|x| x.level <= heading.level)
+			.map_or(self.s.len(), |x| x.line_start);
+		Some(Section { level: heading.level, title: heading.title, body: &self.s[heading.body_start..end] })
+	}
+}
+
+fn find_headings(s: &str) -> Vec<Heading<'_>> {
+	let mut headings = Vec::new();
+	let mut depth = 0;
+	let mut is_line_start = true;
+	let mut char_indices = s.char_indices();
+	while let Some((i, c)) = char_indices.next() {
+		if is_line_start && depth == 0 && c == '=' {
+			let line_end = s[i..].find('\n').map_or(s.len(), |x| i + x);
+			if let Some((level, title)) = parse_heading(&s[i..line_end]) {
+				let body_start = (line_end + '\n'.len_utf8()).min(s.len());
+				headings.push(Heading { level, title, line_start: i, body_start });
+			}
+		}
+		is_line_start = c == '\n';
+		match c {
+			'{' if try_consume(&mut char_indices, &['{']) => depth += 1,
+			'}' if depth > 0 && try_consume(&mut char_indices, &['}']) => depth -= 1,
+			'<' if try_consume(&mut char_indices, &['!', '-', '-']) => {
+				let Some(end) = s[i..].find("-->") else { break };
+				for _ in 0..s[i + "<!--".len()..i + end].chars().count() + "-->".len() {
+					char_indices.next();
+				}
+			},
+			_ => (),
+		}
+	}
+	headings
+}
+
+// Parse a heading line into its level and title. Levels beyond 6 are treated as 6, with the excess
+// equals signs kept in the title, as in MediaWiki.
+fn parse_heading(line: &str) -> Option<(u8, &str)> {
+	// NOTE: Headings may be followed by spaces or a carriage return.
+	let line = line.trim_end();
+	let open = line.len() - line.trim_start_matches('=').len();
+	let close = line.len() - line.trim_end_matches('=').len();
+	let level = open.min(close).min(6);
+	if level < 2 || line.len() < 2 * level {
+		return None;
+	}
+	let title = line[level..line.len() - level].trim();
+	(!title.is_empty()).then_some((level as u8, title))
+}
+
+// A wikilink [[target]] or [[target|display]].
+#[derive(Debug)]
+pub struct Link {
+	pub target: String,
+	pub display: Option<String>,
+	pub byte_range: Range<usize>, // The range of the whole link, including its enclosing brackets.
+}
+
+impl Link {
+	// The namespace or interwiki prefix of the target (e.g. "w" in [[w:Tokyo]]), if any.
+	pub fn namespace(&self) -> Option<&str> {
+		self.target.split_once(':').map(|(namespace, _)| namespace)
+	}
+
+	// The target without its namespace or interwiki prefix.
+	pub fn page(&self) -> &str {
+		self.target.split_once(':').map_or(&self.target, |(_, page)| page)
+	}
+
+	// Whether the link embeds a file or categorizes the page, rather than displaying any text.
+	pub fn is_hidden(&self) -> bool {
+		matches!(self.namespace(), Some("File" | "file" | "Image" | "image" | "Category" | "category"))
+	}
+}
+
+// Find the top-level wikilinks in a string. Unterminated links are ignored.
+pub fn parse_links(s: &str) -> Vec<Link> {
+	let mut links = Vec::new();
+	let mut start = None;
+	let mut depth = 0usize;
+	let mut char_indices = s.char_indices();
 	while let Some((i, c)) = char_indices.next() {
 		if c == '[' && try_consume(&mut char_indices, &[c]) {
-			let Some(x) = reading[i + 2..].find("]]") else { return buffer };
-			let inner = &reading[i + 2..i + 2 + x];
-			for _ in 0..inner.chars().count() + "]]".len() {
-				char_indices.next();
+			if depth == 0 {
+				start = Some(i);
 			}
-			if let Some((_, b)) = inner.split_once('|') {
-				buffer.push_str(b);
-			} else {
-				buffer.push_str(inner);
+			depth += 1;
+		} else if c == ']' && depth > 0 && try_consume(&mut char_indices, &[c]) {
+			depth -= 1;
+			if depth == 0
+				&& let Some(start) = start.take()
+			{
+				let inner = &s[start + 2..i];
+				let (target, display) = match inner.split_once('|') {
+					Some((target, display)) => (target, Some(display.to_owned())),
+					None => (inner, None),
+				};
+				links.push(Link { target: target.to_owned(), display, byte_range: start..i + 2 });
 			}
-			continue;
 		}
+	}
+	links
+}
+
+// Decode decimal, hexadecimal, and a few named character references, leaving unknown ones as is.
+pub fn decode_entities(text: &str) -> Cow<'_, str> {
+	if !text.contains('&') {
+		return Cow::Borrowed(text);
+	}
+	let mut buffer = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find('&') {
+		buffer.push_str(&rest[..start]);
+		rest = &rest[start..];
+		match rest.find(';').and_then(|end| Some((decode_entity(&rest[1..end])?, end))) {
+			Some((c, end)) => {
+				buffer.push(c);
+				rest = &rest[end + ';'.len_utf8()..];
+			},
+			None => {
+				buffer.push('&');
+				rest = &rest['&'.len_utf8()..];
+			},
+		}
+	}
+	buffer.push_str(rest);
+	Cow::Owned(buffer)
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+	let code = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+		u32::from_str_radix(hex, 16).ok()?
+	} else if let Some(decimal) = entity.strip_prefix('#') {
+		decimal.parse().ok()?
+	} else {
+		return match entity {
+			"amp" => Some('&'),
+			"lt" => Some('<'),
+			"gt" => Some('>'),
+			"quot" => Some('"'),
+			"apos" => Some('\''),
+			"nbsp" => Some('\u{A0}'),
+			"ndash" => Some('\u{2013}'),
+			"mdash" => Some('\u{2014}'),
+			_ => None,
+		};
+	};
+	char::from_u32(code)
+}
+
+// Remove HTML tags (e.g. <sup>, </small>, <br/>) from a string, keeping their inner text.
+// Line breaks become spaces, and a '<' that does not begin a tag is kept as is.
+pub fn strip_html_tags(text: &str) -> String {
+	let mut buffer = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find('<') {
+		buffer.push_str(&rest[..start]);
+		rest = &rest[start..];
+		match parse_html_tag(rest) {
+			Some((name, length)) => {
+				if name.eq_ignore_ascii_case("br") {
+					buffer.push(' ');
+				}
+				rest = &rest[length..];
+			},
+			None => {
+				buffer.push('<');
+				rest = &rest['<'.len_utf8()..];
+			},
+		}
+	}
+	buffer.push_str(rest);
+	buffer
+}
 
-		buffer.push(c);
+// Parse an opening, closing, or self-closing tag at the start of a string, returning its name and length.
+fn parse_html_tag(s: &str) -> Option<(&str, usize)> {
+	let inner = s.strip_prefix('<')?;
+	let inner = inner.strip_prefix('/').unwrap_or(inner);
+	let name_length = inner.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(inner.len());
+	let name = &inner[..name_length];
+	if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+		return None;
 	}
+	let attributes = &inner[name_length..];
+	if !attributes.is_empty() && !attributes.starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>')
+	{
+		return None;
+	}
+	let end = attributes.find(['<', '>']).filter(|&i| attributes[i..].starts_with('>'))?;
+	Some((name, s.len() - attributes.len() + end + '>'.len_utf8()))
+}
+
+// Remove [[...]] and [[...|...]] from a string, keeping their displayed text.
+// File, image, and category links are removed entirely.
+pub fn remove_links(reading: &str) -> String {
+	let mut buffer = String::new();
+	let mut end = 0;
+	for link in parse_links(reading) {
+		buffer.push_str(&reading[end..link.byte_range.start]);
+		match &link.display {
+			_ if link.is_hidden() => (),
+			Some(display) => buffer.push_str(&remove_links(display)),
+			None => buffer.push_str(&link.target),
+		}
+		end = link.byte_range.end;
+	}
+	buffer.push_str(&reading[end..]);
 	buffer
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn names(s: &str) -> Vec<String> {
+		FindTemplates::new(s).map(|x| x.unwrap().0.into_owned()).collect()
+	}
+
+	#[test]
+	fn recovering_resumes_after_malformed_wikitext() {
+		let templates: Vec<_> = FindTemplates::new("}}{{ja-pron|あめ}}").recovering().collect();
+		assert!(matches!(
+			templates[0],
+			Err(TemplateError { offset: 0, kind: TemplateErrorKind::UnbalancedClose })
+		));
+		assert!(matches!(&templates[1..], [Ok((name, "あめ"))] if name == "ja-pron"));
+	}
+
+	#[test]
+	fn parser_functions_are_skipped() {
+		assert_eq!(names("{{#switch:x|a=1|b=2}}"), Vec::<String>::new());
+		assert_eq!(names("{{#switch:x|a=1|b=2}}{{ja-pron|とうきょう}}"), ["ja-pron"]);
+	}
+
+	#[test]
+	fn parser_function_names_may_contain_parameters() {
+		let templates: Vec<_> =
+			FindTemplates::new("{{#if:{{{1|}}}|yes|no}}{{ja-pron|とうきょう}}").map(Result::unwrap).collect();
+		assert_eq!(templates.len(), 1);
+		assert_eq!(templates[0].0, "ja-pron");
+		assert_eq!(templates[0].1, "とうきょう");
+	}
+
+	#[test]
+	fn templates_are_spanned_by_their_braces() {
+		let s = "a {{ja-pron|あめ}} b {{ja-noun}}";
+		let spans: Vec<_> = FindTemplates::new(s).spanned().map(|x| x.unwrap().2).collect();
+		assert_eq!(spans, [2..20, 23..34]);
+		assert_eq!(&s[spans[0].clone()], "{{ja-pron|あめ}}");
+	}
+
+	// Each parameter as a name, or its number if positional, and its value.
+	fn parameters(s: &str) -> Vec<(String, String)> {
+		NamedParameters::new(s)
+			.map(|param| match param {
+				Param::Named(name, value) => (name.into_owned(), value.into_owned()),
+				Param::Positional(n, value) => (n.to_string(), value.into_owned()),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn parameters_are_numbered_and_named() {
+		let expected = [("1", "て"), ("k2", "がみ"), ("2", "かみ"), ("4", "x"), ("3", "[[a|b=c]]")];
+		let expected = expected.map(|(name, value)| (name.to_owned(), value.to_owned()));
+		assert_eq!(parameters("て|k2=がみ|かみ|4=x|[[a|b=c]]"), expected);
+		let nested = parameters("{{ja-r|a=b}}|c=d");
+		assert_eq!(nested, [("1".to_owned(), "{{ja-r|a=b}}".to_owned()), ("c".to_owned(), "d".to_owned())]);
+		assert!(parameters("").is_empty());
+	}
+
+	fn error(s: &str) -> Option<(usize, TemplateErrorKind)> {
+		FindTemplates::new(s).find_map(Result::err).map(|e| (e.offset, e.kind))
+	}
+
+	#[test]
+	fn malformed_wikitext_is_reported() {
+		assert!(matches!(error("a}}"), Some((1, TemplateErrorKind::UnbalancedClose))));
+		assert!(matches!(error("a{{ja-pron|あめ"), Some((1, TemplateErrorKind::UnterminatedTemplate))));
+		assert!(matches!(error("a<!-- x"), Some((1, TemplateErrorKind::UnterminatedComment))));
+		assert!(error("{{ja-pron|あめ}}").is_none());
+		// The iterator stops after an error.
+		assert_eq!(FindTemplates::new("}}{{ja-pron}}").count(), 1);
+	}
+
+	#[test]
+	fn parameter_references_are_kept_whole() {
+		let templates: Vec<_> =
+			FindTemplates::new("{{ja-pron|{{{1|トウキョウ}}}}}").map(Result::unwrap).collect();
+		assert!(matches!(&templates[..], [(name, "{{{1|トウキョウ}}}")] if name == "ja-pron"));
+		assert_eq!(
+			parameters("{{{1|トウキョウ}}}|acc=0")[0],
+			("1".to_owned(), "{{{1|トウキョウ}}}".to_owned())
+		);
+		// A reference may close together with the template that holds it.
+		assert_eq!(names("{{a|{{b|{{{1}}}}}}}"), ["a"]);
+	}
+
+	#[test]
+	fn arguments_are_named_only_by_an_equals_sign_outside_nesting() {
+		let arguments: Vec<_> = TemplateArguments::trimmed(" a | k = v=w |[[x|y=z]]|{{t|m=n}}")
+			.map(|argument| match argument {
+				Arg::Positional(value) => (None, value.into_owned()),
+				Arg::Named { key, value } => (Some(key.into_owned()), value.into_owned()),
+			})
+			.collect();
+		let expected = [(None, "a"), (Some("k"), "v=w"), (None, "[[x|y=z]]"), (None, "{{t|m=n}}")];
+		assert_eq!(arguments, expected.map(|(key, value)| (key.map(str::to_owned), value.to_owned())));
+	}
+
+	#[test]
+	fn nowiki_content_is_skipped() {
+		assert_eq!(names("{{ja-pron|あめ}}<nowiki>{{ja-pron|fake}}</nowiki>"), ["ja-pron"]);
+		assert_eq!(names("{{a}}<nowiki/>{{b}}"), ["a", "b"]);
+		let templates: Vec<_> = FindTemplates::new("{{a}}<nowiki>{{b}}").collect();
+		assert!(matches!(
+			&templates[..],
+			[Ok(_), Err(TemplateError { offset: 5, kind: TemplateErrorKind::UnterminatedNowiki })]
+		));
+	}
+
+	#[test]
+	fn wikilinks_give_their_target_and_display() {
+		let links = parse_links("[[雨]] and [[雨|あめ]], [[w:Tokyo]], [[a|[[b]] c]], [[unterminated");
+		let parts: Vec<_> = links.iter().map(|x| (x.namespace(), x.page(), x.display.as_deref())).collect();
+		assert_eq!(
+			parts,
+			[
+				(None, "雨", None),
+				(None, "雨", Some("あめ")),
+				(Some("w"), "Tokyo", None),
+				(None, "a", Some("[[b]] c"))
+			]
+		);
+		assert_eq!(links[0].byte_range, 0..7);
+	}
+
+	#[test]
+	fn hidden_links_are_removed_entirely() {
+		assert_eq!(remove_links("[[雨|あめ]][[File:Ame.jpg|thumb|rain]]"), "あめ");
+		assert_eq!(remove_links("あめ[[Category:ja:Weather]]"), "あめ");
+		assert_eq!(remove_links("[[Image:x.png]][[あめ]]"), "あめ");
+		assert_eq!(remove_links("[[w:雨|あめ]]"), "あめ");
+	}
+
+	#[test]
+	fn trimmed_parameters_lose_their_surrounding_whitespace() {
+		let parameters: Vec<_> = TemplateParameters::trimmed(" a | acc = 0 |\u{3000}b\n").collect();
+		assert_eq!(parameters, ["a", "acc = 0", "b"]);
+		// Whitespace left around a removed comment is trimmed too.
+		let parameters: Vec<_> = TemplateParameters::trimmed(" a <!-- x --> | b").collect();
+		assert_eq!(parameters, ["a", "b"]);
+		let parameters: Vec<_> = TemplateParameters::new(" a | b").collect();
+		assert_eq!(parameters, [" a ", " b"]);
+	}
+
+	#[test]
+	fn comments_are_stripped_from_template_names() {
+		assert_eq!(names("{{<!-- xxx -->ja-pron|あめ}}"), ["ja-pron"]);
+		assert_eq!(names("{{ja-<!-- xxx -->pron|あめ}}"), ["ja-pron"]);
+		assert_eq!(names("{{ja-pron<!-- xxx -->}}"), ["ja-pron"]);
+	}
+
+	#[test]
+	fn html_tags_are_stripped_but_their_text_is_kept() {
+		assert_eq!(strip_html_tags("あ<sup>1</sup>め"), "あ1め");
+		assert_eq!(strip_html_tags("<small class=\"x\">あめ</small><br/>ふる"), "あめ ふる");
+		assert_eq!(strip_html_tags("あめ<br>ふる"), "あめ ふる");
+		assert_eq!(strip_html_tags("a < b <1> <c"), "a < b <1> <c");
+	}
+
+	#[test]
+	fn character_references_are_decoded() {
+		assert_eq!(decode_entities("と&#x30FC;きょう"), "とーきょう");
+		assert_eq!(decode_entities("a&#124;b&amp;c&nbsp;d"), "a|b&c\u{A0}d");
+		assert_eq!(decode_entities("&unknown; & &#xZZ;"), "&unknown; & &#xZZ;");
+		assert!(matches!(decode_entities("あめ"), Cow::Borrowed("あめ")));
+	}
+
+	#[test]
+	fn template_trees_hold_nested_templates() {
+		let nodes = parse_template_tree("{{ja-usex|{{l|ja|雨}} が 降る|t=It rains.}}");
+		let [TemplateNode { name, params }] = &nodes[..] else { panic!("expected one template: {nodes:?}") };
+		assert_eq!(name, "ja-usex");
+		let [TemplateParam::Positional(1, value), TemplateParam::Named(key, translation)] = &params[..]
+		else {
+			panic!("unexpected parameters: {params:?}")
+		};
+		assert_eq!(key, "t");
+		assert!(matches!(&translation[..], [TemplateValue::Text(text)] if text == "It rains."));
+		let [TemplateValue::Template(link), TemplateValue::Text(text)] = &value[..] else {
+			panic!("unexpected value: {value:?}")
+		};
+		assert_eq!(text, " が 降る");
+		assert_eq!(link.name, "l");
+		assert!(matches!(
+			&link.params[..],
+			[TemplateParam::Positional(1, a), TemplateParam::Positional(2, b)]
+				if matches!(&a[..], [TemplateValue::Text(x)] if x == "ja")
+					&& matches!(&b[..], [TemplateValue::Text(x)] if x == "雨")
+		));
+	}
+
+	#[test]
+	fn deeply_nested_templates_stop_the_scan() {
+		let s = "{{a|".repeat(1000);
+		let templates: Vec<_> = FindTemplates::new(&s).collect();
+		assert!(matches!(
+			&templates[..],
+			[Err(TemplateError { offset: 256, kind: TemplateErrorKind::TooDeep })]
+		));
+		let s = "{{a|{{b|{{c}}}}}}";
+		assert_eq!(names(s), ["a"]);
+		let templates: Vec<_> = FindTemplates::with_max_depth(s, 2).collect();
+		assert!(matches!(
+			&templates[..],
+			[Err(TemplateError { offset: 8, kind: TemplateErrorKind::TooDeep })]
+		));
+	}
+
+	#[test]
+	fn sections_span_their_subsections() {
+		let page = "==Japanese==\n===Etymology 1===\na\n<!--\n==Fake==\n-->{{t|\n==Fake==\n}}\n===Etymology 2===\nb\n==Korean==\nc";
+		let sections: Vec<_> = Sections::new(page).map(|x| (x.level, x.title, x.body)).collect();
+		assert_eq!(
+			sections,
+			[
+				(2, "Japanese", &page[13..page.find("==Korean==").unwrap()]),
+				(3, "Etymology 1", "a\n<!--\n==Fake==\n-->{{t|\n==Fake==\n}}\n"),
+				(3, "Etymology 2", "b\n"),
+				(2, "Korean", "c"),
+			]
+		);
+	}
+
+	#[test]
+	fn headings_may_end_with_whitespace() {
+		let page = "==Japanese== \r\n===Etymology 1===\t\r\na\r\n";
+		let sections: Vec<_> = Sections::new(page).map(|x| (x.level, x.title, x.body)).collect();
+		assert_eq!(sections, [(2, "Japanese", &page[15..]), (3, "Etymology 1", "a\r\n")]);
+	}
+
+	#[test]
+	fn recovering_reports_each_unclosed_construct_once() {
+		let templates: Vec<_> = FindTemplates::new("{{a|{{b}}}} {{{x").recovering().collect();
+		assert!(matches!(&templates[0], Ok((name, "{{b}}")) if name == "a"));
+		assert!(matches!(
+			&templates[1..],
+			[Err(TemplateError { offset: 12, kind: TemplateErrorKind::UnterminatedTemplate })]
+		));
+	}
+}
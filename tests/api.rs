@@ -0,0 +1,32 @@
+use wiktionary_hatsuon::{
+	AccentConflictPolicy, Extraction, Extractor, build_info, diagnostics::Diagnostics, process_page,
+	resolve_redirects,
+};
+
+const PAGES: [(&str, &str); 2] = [
+	("手帖", "==Japanese==\n{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-see|手紙}}\n"),
+	("手紙", "==Japanese==\n{{ja-kanjitab|て|かみ|k2=がみ}}\n{{ja-pron|てがみ|acc=0}}\n{{ja-noun|てがみ}}\n"),
+];
+
+#[test]
+fn pages_may_be_built_from_any_source() {
+	let info = build_info(PAGES.iter().map(|(title, text)| (title.to_string(), text.to_string())));
+	assert_eq!(info["手紙"].reading_infos["テガミ"].accents, [0]);
+	// A redirect takes the readings of the title it sees.
+	let decomposition = info["手帖"].reading_infos["テガミ"].decomposition.as_ref().unwrap();
+	assert_eq!(decomposition.reading(), "テガミ");
+}
+
+#[test]
+fn pages_may_be_processed_one_by_one() {
+	let extractor = Extractor::new(AccentConflictPolicy::KeepAll);
+	let diagnostics = Diagnostics::new();
+	let mut extraction = Extraction::default();
+	for (title, text) in PAGES {
+		process_page(title, text, &mut extraction, &extractor, &diagnostics);
+	}
+	assert_eq!(extraction.redirects.len(), 1);
+	assert!(!extraction.info.contains_key("手帖"));
+	resolve_redirects(extraction.redirects, &mut extraction.info, &diagnostics);
+	assert!(extraction.info.contains_key("手帖"));
+}
@@ -0,0 +1,238 @@
+use std::{
+	path::{Path, PathBuf},
+	process::{Command, Output},
+};
+
+use wiktionary_hatsuon::{Record, build_info};
+
+const PAGES: [(&str, &str); 2] = [
+	(
+		"手紙",
+		"==Japanese==\n{{ja-kanjitab|て|かみ|k2=がみ}}\n===Pronunciation===\n{{ja-pron|てがみ|acc=0}}\n\
+		 ====Noun====\n{{ja-noun|てがみ}}\n",
+	),
+	("dog", "==English==\n"),
+];
+
+// Write the pages as a filtered dump, as scripts/filter_wiktionary.rs does.
+fn write_fixture(name: &str, pages: &[(&str, &str)]) -> PathBuf {
+	let pages: Vec<_> = pages.iter().map(|(title, text)| (title.as_bytes(), text.as_bytes())).collect();
+	write_raw_fixture(name, &pages, &[])
+}
+
+// Write the pages as a filtered dump, even if they are not UTF-8, followed by some trailing bytes.
+fn write_raw_fixture(name: &str, pages: &[(&[u8], &[u8])], trailer: &[u8]) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("hatsuon-{name}-{}.bin", std::process::id()));
+	let mut records = Vec::new();
+	for (title, text) in pages {
+		for field in [title, text] {
+			records.extend((field.len() as u64).to_le_bytes());
+			records.extend(*field);
+		}
+	}
+	records.extend(trailer);
+	std::fs::write(&path, records).unwrap();
+	path
+}
+
+fn run(args: &[&str], input: Option<&Path>) -> Output {
+	let mut command = Command::new(env!("CARGO_BIN_EXE_wiktionary-hatsuon"));
+	command.args(args).env_remove("HATSUON_INPUT");
+	if let Some(input) = input {
+		command.env("HATSUON_INPUT", input);
+	}
+	command.output().unwrap()
+}
+
+fn expected_record() -> serde_json::Value {
+	serde_json::json!({
+		"title": "手紙",
+		"kana_only": false,
+		"readings": [{
+			"reading": "テガミ",
+			"accents": [0],
+			"accent_conflict": false,
+			"predicted_accent": null,
+			"decomposition": {
+				"atoms": [
+					{ "kind": "ruby", "value": { "character_count": 1, "base": "手", "reading": "テ" } },
+					{
+						"kind": "ruby",
+						"value": { "character_count": 1, "base": "紙", "reading": "ガミ", "sound_change": "rendaku" },
+					},
+				],
+				"reference": null,
+			},
+		}],
+	})
+}
+
+#[test]
+fn extracted_records_serialize_as_expected() {
+	let info = build_info(PAGES.iter().map(|(title, text)| (title.to_string(), text.to_string())));
+	assert_eq!(info.len(), 1);
+	assert_eq!(serde_json::to_value(Record::new("手紙", &info["手紙"])).unwrap(), expected_record());
+}
+
+#[test]
+fn input_is_read_from_the_arguments() {
+	let path = write_fixture("arguments", &PAGES);
+	let output = run(&["--format", "json-lines", path.to_str().unwrap()], None);
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let records: Vec<serde_json::Value> =
+		String::from_utf8(output.stdout).unwrap().lines().map(|x| serde_json::from_str(x).unwrap()).collect();
+	assert_eq!(records, [expected_record()]);
+}
+
+#[test]
+fn input_may_be_given_by_the_environment() {
+	let path = write_fixture("environment", &PAGES);
+	let output = run(&[], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	assert_eq!(serde_json::from_str::<serde_json::Value>(stdout.trim_end()).unwrap(), expected_record());
+}
+
+#[test]
+fn summaries_count_the_titles() {
+	let path = write_fixture("summary", &PAGES);
+	let output = run(&["--format", "summary"], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+}
+
+#[test]
+fn missing_input_prints_the_usage() {
+	let output = run(&[], None);
+	assert_eq!(output.status.code(), Some(2));
+	assert!(String::from_utf8(output.stderr).unwrap().starts_with("usage: "));
+}
+
+#[test]
+fn entries_may_be_filtered_by_kanji() {
+	let pages = [
+		("学校", "==Japanese==\n{{ja-pron|がっこう|acc=0}}\n"),
+		("大学", "==Japanese==\n{{ja-pron|だいがく|acc=0}}\n"),
+		("日本", "==Japanese==\n{{ja-pron|にほん|acc=2}}\n"),
+		("学", "==Japanese==\n{{ja-pron|がく|acc=1}}\n"),
+		("雨", "==Japanese==\n{{ja-pron|あめ|acc=1}}\n"),
+	];
+	let path = write_fixture("contains-kanji", &pages);
+	let output =
+		run(&["--format", "json-lines", "--contains-kanji", "学", "--contains-kanji", "雨"], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let mut titles: Vec<_> = stdout
+		.lines()
+		.map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap()["title"].as_str().unwrap().to_owned())
+		.collect();
+	titles.sort_unstable();
+	assert_eq!(titles, ["大学", "学", "学校", "雨"]);
+}
+
+#[test]
+fn warnings_may_be_given_with_their_entries() {
+	let pages = [("東京", "==Japanese==\n{{ja-pron|とうきょう|acc=0|2=ときょ|acc2=9}}\n")];
+	let path = write_fixture("inline-warnings", &pages);
+	let records = |args: &[&str]| -> Vec<serde_json::Value> {
+		let output = run(args, Some(&path));
+		assert!(output.status.success());
+		String::from_utf8(output.stdout).unwrap().lines().map(|x| serde_json::from_str(x).unwrap()).collect()
+	};
+	let inline = records(&["--format", "json-lines", "--inline-warnings"]);
+	let plain = records(&["--format", "json-lines"]);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(inline[0]["warnings"], serde_json::json!(["accent 9 exceeds the 2 moras of reading トキョ"]));
+	assert!(plain[0].get("warnings").is_none());
+}
+
+#[test]
+fn json_lines_give_one_record_per_line_by_title() {
+	let pages = [
+		("雨", "==Japanese==\n{{ja-pron|あめ|acc=1}}\n"),
+		("手紙", PAGES[0].1),
+		("あめ", "==Japanese==\n{{ja-pron|あめ|acc=1}}\n"),
+	];
+	let path = write_fixture("json-lines", &pages);
+	let output = run(&["--format", "json-lines"], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let records: Vec<serde_json::Value> = stdout.lines().map(|x| serde_json::from_str(x).unwrap()).collect();
+	let titles: Vec<_> = records.iter().map(|x| x["title"].as_str().unwrap()).collect();
+	assert_eq!(titles, ["あめ", "手紙", "雨"]);
+	assert_eq!(records[1], expected_record());
+	assert_eq!(records[2]["readings"][0]["accents"], serde_json::json!([1]));
+}
+
+#[test]
+fn corrupt_records_are_skipped() {
+	let pages: [(&[u8], &[u8]); 3] = [
+		("雨".as_bytes(), "==Japanese==\n{{ja-pron|あめ|acc=1}}\n".as_bytes()),
+		("飴".as_bytes(), b"==Japanese==\n\xFF\xFE"),
+		(PAGES[0].0.as_bytes(), PAGES[0].1.as_bytes()),
+	];
+	// The final record is cut short in its length prefix.
+	let path = write_raw_fixture("corrupt", &pages, &[1, 0, 0]);
+	let output = run(&["--format", "json-lines"], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let records: Vec<serde_json::Value> = stdout.lines().map(|x| serde_json::from_str(x).unwrap()).collect();
+	let titles: Vec<_> = records.iter().map(|x| x["title"].as_str().unwrap()).collect();
+	assert_eq!(titles, ["手紙", "雨"]);
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("skipped record 飴 at byte 57: invalid UTF-8"), "{stderr}");
+	assert!(stderr.contains("stopped reading at byte"), "{stderr}");
+}
+
+// Parse CSV as per RFC 4180, into rows of fields.
+fn parse_csv(s: &str) -> Vec<Vec<String>> {
+	let mut rows = Vec::new();
+	let mut row = Vec::new();
+	let mut field = String::new();
+	let mut is_quoted = false;
+	let mut chars = s.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'"' if is_quoted && chars.peek() == Some(&'"') => {
+				chars.next();
+				field.push('"');
+			},
+			'"' => is_quoted = !is_quoted,
+			',' if !is_quoted => row.push(std::mem::take(&mut field)),
+			'\n' if !is_quoted => {
+				row.push(std::mem::take(&mut field));
+				rows.push(std::mem::take(&mut row));
+			},
+			_ => field.push(c),
+		}
+	}
+	rows
+}
+
+#[test]
+fn csv_fields_are_escaped() {
+	let pages = [
+		("雨,\"あめ\"", "==Japanese==\n{{ja-pron|あめ|あめ|acc=1|acc2=0|acc2_loc=Kyoto}}\n"),
+		("手紙", PAGES[0].1),
+		("飴", "==Japanese==\n{{ja-kanjitab|あめ}}\n{{ja-pron|あめ}}\n"),
+	];
+	let path = write_fixture("csv", &pages);
+	let output = run(&["--format", "csv"], Some(&path));
+	std::fs::remove_file(&path).unwrap();
+	assert!(output.status.success());
+	let rows = parse_csv(&String::from_utf8(output.stdout).unwrap());
+	let expected = [
+		["title", "reading", "accent", "dialect"],
+		["手紙", "テガミ", "0", "tokyo"],
+		["雨,\"あめ\"", "アメ", "1", "tokyo"],
+		["雨,\"あめ\"", "アメ", "0", "keihan"],
+		["飴", "アメ", "", ""],
+	];
+	assert_eq!(rows, expected.map(|row| row.map(str::to_owned).to_vec()));
+}